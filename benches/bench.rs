@@ -1,5 +1,4 @@
 #![feature(test)]
-#![cfg_attr(feature = "generators", feature(generators, generator_trait))]
 
 extern crate test;
 extern crate pem_iterator;
@@ -8,14 +7,11 @@ extern crate rand;
 
 use std::iter::repeat;
 
-#[cfg(feature = "generators")]
-use std::ops::{Generator, GeneratorState};
-
 use pem_iterator::boundary::{BoundaryType, BoundaryParser, LabelMatcher};
 use pem_iterator::body::{Chunked, Single, ResultBytes};
 #[cfg(feature = "generators")]
 use pem_iterator::generator::{parse_boundary_chars, parse_body_chunked_chars,
-                              parse_body_single_chars};
+                              parse_body_single_chars, Generator, GeneratorState};
 use test::{Bencher, black_box};
 use rand::{Rng, weak_rng};
 
@@ -1,11 +1,12 @@
 use core::iter::Map;
+use core::fmt;
 
 use core::borrow::{Borrow, BorrowMut};
 use core::slice;
  
 use core::iter::FromIterator;
 
-use {Void, map_chars, is_whitespace};
+use {Void, MapBytes, map_chars, map_bytes, is_whitespace};
 
 #[derive(Debug, PartialEq)]
 pub enum BodyError<Loc, E> {
@@ -14,15 +15,171 @@ pub enum BodyError<Loc, E> {
         found: char
     },
     MissingExpected(char),
+    /// `'='` padding was missing, misplaced, or didn't complete the final quantum
+    InvalidPadding{
+        location: Loc,
+    },
+    /// The body ended with a lone sextet, or with non-zero bits left over from the final quantum
+    NonCanonical{
+        location: Loc,
+    },
+    /// A line was longer than `max_line_length`, at its first extra character
+    LineTooLong{
+        location: Loc,
+    },
+    /// With `exact_lines`, a line before the last was shorter than `max_line_length`, detected at the start of the next line
+    LineTooShort{
+        location: Loc,
+    },
+    /// With `Lines::Strict`, a line had no characters, at its line break
+    EmptyLine{
+        location: Loc,
+    },
+    /// With `Lines::Strict`, the `END` boundary came before any of the body, at its first `'-'`
+    EmptyBody{
+        location: Loc,
+    },
+    /// With `Whitespace::Trailing`, a space or tab was followed by more of the line, at the character after it
+    MisplacedWhitespace{
+        location: Loc,
+    },
     SourceError(E),
 }
 
+#[cfg(feature = "std")]
+impl<Loc, E> BodyError<Loc, E> {
+    /// Splits off a `SourceError`, so the remaining errors can take another stream error type
+    pub(crate) fn without_source<F>(self) -> Result<BodyError<Loc, F>, E> {
+        use self::BodyError::*;
+        Ok(match self {
+            SourceError(e) => return Err(e),
+            InvalidCharacter{location, found} => InvalidCharacter{location, found},
+            MissingExpected(c) => MissingExpected(c),
+            InvalidPadding{location} => InvalidPadding{location},
+            NonCanonical{location} => NonCanonical{location},
+            LineTooLong{location} => LineTooLong{location},
+            LineTooShort{location} => LineTooShort{location},
+            EmptyLine{location} => EmptyLine{location},
+            EmptyBody{location} => EmptyBody{location},
+            MisplacedWhitespace{location} => MisplacedWhitespace{location},
+        })
+    }
+}
+
 impl<Location, E> From<E> for BodyError<Location, E> {
     fn from(e: E) -> Self {
         BodyError::SourceError(e)
     }
 }
 
+impl<Loc: fmt::Display, E> fmt::Display for BodyError<Loc, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::BodyError::*;
+        match *self {
+            InvalidCharacter{ref location, found} => write!(f, "invalid character {:?} at {}", found, location),
+            MissingExpected(expected) => write!(f, "expected {:?}, found the end of the input", expected),
+            InvalidPadding{ref location} => write!(f, "invalid padding at {}", location),
+            NonCanonical{ref location} => write!(f, "non-canonical encoding ending at {}", location),
+            LineTooLong{ref location} => write!(f, "line too long at {}", location),
+            LineTooShort{ref location} => write!(f, "line too short before {}", location),
+            EmptyLine{ref location} => write!(f, "empty line at {}", location),
+            EmptyBody{ref location} => write!(f, "empty body at {}", location),
+            MisplacedWhitespace{ref location} => write!(f, "whitespace before the end of the line at {}", location),
+            SourceError(_) => f.write_str("error reading the input"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Loc, E> ::std::error::Error for BodyError<Loc, E>
+where Loc: fmt::Debug + fmt::Display,
+    E: ::std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn (::std::error::Error) + 'static)> {
+        match *self {
+            BodyError::SourceError(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// How `'='` padding is handled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Padding {
+    /// `'='` is ignored wherever it appears
+    Ignore,
+    /// `'='` may only appear at the end of the body, and must bring the final quantum to 4 characters
+    Strict,
+    /// Like `Strict`, but some or all of the `'='` may be left out
+    Optional,
+}
+
+/// Which whitespace may appear in the body, besides `'\n'` and `'\r'`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Whitespace {
+    /// Only line breaks
+    LineBreaks,
+    /// Spaces and tabs, but only at the end of a line
+    Trailing,
+    /// Spaces, tabs, vertical tabs, and form feeds
+    Ascii,
+    /// Any Unicode whitespace
+    Unicode,
+}
+
+impl Whitespace {
+    fn allows<Loc>(self, c: &(Loc, char)) -> bool {
+        match self {
+            Whitespace::LineBreaks => false,
+            Whitespace::Trailing => matches!(c.1, ' ' | '\t'),
+            Whitespace::Ascii => matches!(c.1, ' ' | '\t' | '\x0B' | '\x0C'),
+            Whitespace::Unicode => is_whitespace(c),
+        }
+    }
+}
+
+/// Where line breaks must appear in the body
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lines {
+    /// Anywhere, and the `END` boundary may follow the body on the same line
+    Any,
+    /// Anywhere, but the `END` boundary must start a line
+    EndOnNewLine,
+    /// Like `EndOnNewLine`, but the body must not be empty, and neither may any of its lines.
+    ///
+    /// A `'\n'` right after the `BEGIN` boundary is taken as the end of its `"\r\n"`, so one empty line there goes unnoticed after a plain `'\n'`.
+    Strict,
+}
+
+/// Controls how strictly the body is parsed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BodyConfig {
+    pub padding: Padding,
+    /// Reject bodies with more than one encoding of the same bytes
+    pub canonical: bool,
+    /// The most characters allowed on a line, not counting whitespace, or 0 for no limit
+    pub max_line_length: usize,
+    /// Require every line but the last to be exactly `max_line_length` characters. Has no effect without a `max_line_length`.
+    pub exact_lines: bool,
+    /// Other whitespace is reported as an `InvalidCharacter`
+    pub whitespace: Whitespace,
+    /// A misplaced `END` boundary is reported as an `InvalidCharacter` at its first `'-'`
+    pub lines: Lines,
+}
+
+impl Default for BodyConfig {
+    fn default() -> Self {
+        BodyConfig{
+            padding: Padding::Ignore,
+            canonical: false,
+            max_line_length: 0,
+            exact_lines: false,
+            whitespace: Whitespace::Unicode,
+            lines: Lines::Any,
+        }
+    }
+}
+
 
 
 
@@ -255,36 +412,152 @@ impl<Loc, E> ExactSizeIterator for ResultBytesIter<Loc, E> {}
 
 
 
-pub(crate) fn get_6_bits<Location, E>(
-    stream: &mut Iterator<Item = Result<(Location, char), E>>,
-) -> Result<Option<u8>, BodyError<Location, E>> {
-    use self::BodyError::*;
+/// Reads the characters of the body, applying a `BodyConfig`
+pub(crate) struct Sextets {
+    config: BodyConfig,
+    padding: PaddingState,
+    /// The number of characters on the current line
+    column: usize,
+    /// Whether the last line ended short of `max_line_length`
+    short: bool,
+    /// Whether the last character was a `'\r'`, so a `'\n'` ends the same line.
+    /// The `BEGIN` boundary leaves the `'\n'` of a `"\r\n"` to the body, so it starts out set.
+    cr: bool,
+    /// Whether whitespace has been skipped on the current line
+    space: bool,
+    /// Whether nothing but whitespace has been read
+    empty: bool,
+}
+
+#[derive(Clone, Copy)]
+enum PaddingState {
+    Data,
+    Pad{
+        remaining: u8,
+    },
+    Padded,
+}
 
-    // Ignore '=' and whitespace
-    fn ignore<Location>(c: &(Location, char)) -> bool {
-        c.1 != '=' && !is_whitespace(c)
+impl Sextets {
+    pub(crate) fn new(config: BodyConfig) -> Self {
+        Sextets{
+            config, padding: PaddingState::Data, column: 0, short: false, cr: true, space: false, empty: true,
+        }
     }
-    let mut stream = stream.filter(|c| c.as_ref().map(ignore).unwrap_or(true));
 
-    // If the stream ends without a footer, complain
-    let (location, c) = stream.next().ok_or(MissingExpected('-'))??;
+    /// Gets the next 6 bits of the body, or `None` after consuming the first `'-'` of the `END` boundary.
+    ///
+    /// `position` is the number of characters already read in the current quantum,
+    /// and `leftover` is the bits which would be discarded if the body ended here.
+    pub(crate) fn next<'a, Location: 'a, E: 'a>(
+        &mut self,
+        stream: &'a mut dyn Iterator<Item = Result<(Location, char), E>>,
+        position: u8,
+        leftover: u8,
+    ) -> Result<Option<u8>, BodyError<Location, E>> {
+        use self::BodyError::*;
+
+        loop {
+            // If the stream ends without a footer, complain
+            let c = stream.next().ok_or(MissingExpected('-'))??;
+            if c.1 == '\n' || c.1 == '\r' {
+                if self.column != 0 {
+                    self.short = self.config.exact_lines && self.config.max_line_length != 0 && self.column != self.config.max_line_length;
+                    self.column = 0;
+                } else if self.config.lines == Lines::Strict && !(self.cr && c.1 == '\n') {
+                    return Err(EmptyLine{location: c.0});
+                }
+                self.cr = c.1 == '\r';
+                self.space = false;
+                continue;
+            }
+            self.cr = false;
+            if self.config.whitespace.allows(&c) {
+                self.space = true;
+                continue;
+            }
+            let (location, c) = c;
+            if self.space && self.config.whitespace == Whitespace::Trailing {
+                return Err(MisplacedWhitespace{location});
+            }
+
+            // The END boundary may follow a line of any length
+            if c == '-' {
+                if self.column != 0 && self.config.lines != Lines::Any {
+                    return Err(InvalidCharacter{location, found: c});
+                }
+                if self.empty && self.config.lines == Lines::Strict {
+                    return Err(EmptyBody{location});
+                }
+            } else {
+                if self.short {
+                    return Err(LineTooShort{location});
+                }
+                self.column += 1;
+                if self.config.max_line_length != 0 && self.column > self.config.max_line_length {
+                    return Err(LineTooLong{location});
+                }
+                self.empty = false;
+            }
 
-    let (offset, base) = match c {
-        '-' => return Ok(None),
-        'A'...'Z' => (0, 'A'),
-        'a'...'z' => (26, 'a'),
-        '0'...'9' => (52, '0'),
-        '+' => (62, '+'),
-        '/' => (63, '/'),
-        found => return Err(InvalidCharacter{found, location}),
-    };
+            match self.padding {
+                PaddingState::Data => {},
+                PaddingState::Pad{remaining} => if c == '=' {
+                    self.padding = if remaining == 1 {
+                        PaddingState::Padded
+                    } else {
+                        PaddingState::Pad{remaining: remaining - 1}
+                    };
+                    continue;
+                } else if c == '-' && self.config.padding == Padding::Optional {
+                    return Ok(None);
+                } else {
+                    return Err(InvalidPadding{location});
+                },
+                PaddingState::Padded => return if c == '-' {
+                    Ok(None)
+                } else {
+                    Err(InvalidPadding{location})
+                },
+            }
 
-    Ok(Some((offset + c as u32 - base as u32) as u8))
+            let canonical = !self.config.canonical || position == 0 || (position != 1 && leftover == 0);
+            let (offset, base) = match c {
+                '-' => return match self.config.padding {
+                    Padding::Strict if position != 0 => Err(InvalidPadding{location}),
+                    _ if !canonical => Err(NonCanonical{location}),
+                    _ => Ok(None),
+                },
+                '=' => match self.config.padding {
+                    Padding::Ignore => continue,
+                    Padding::Strict | Padding::Optional if position < 2 => return Err(InvalidPadding{location}),
+                    Padding::Strict | Padding::Optional if !canonical => return Err(NonCanonical{location}),
+                    Padding::Strict | Padding::Optional => {
+                        self.padding = if position == 3 {
+                            PaddingState::Padded
+                        } else {
+                            PaddingState::Pad{remaining: 1}
+                        };
+                        continue;
+                    },
+                },
+                'A'...'Z' => (0, 'A'),
+                'a'...'z' => (26, 'a'),
+                '0'...'9' => (52, '0'),
+                '+' => (62, '+'),
+                '/' => (63, '/'),
+                found => return Err(InvalidCharacter{found, location}),
+            };
+
+            return Ok(Some((offset + c as u32 - base as u32) as u8));
+        }
+    }
 }
 
 
 pub struct Chunked<S> {
     stream: S,
+    sextets: Sextets,
     state: Option<ChunkedState>,
 }
 
@@ -305,8 +578,12 @@ impl<Loc, E, S> Chunked<S>
 where S: Iterator<Item = Result<(Loc, char), E>>
 {
     pub fn new(stream: S) -> Self {
+        Self::with_config(stream, BodyConfig::default())
+    }
+
+    pub fn with_config(stream: S, config: BodyConfig) -> Self {
         Chunked{
-            stream, state: Some(ChunkedState::Zero)
+            stream, sextets: Sextets::new(config), state: Some(ChunkedState::Zero)
         }
     }
 }
@@ -317,6 +594,23 @@ where S: Iterator<Item = (Loc, char)>
     pub fn from_chars(stream: S) -> Self {
         Self::new(stream.map(map_chars))
     }
+
+    pub fn from_chars_with_config(stream: S, config: BodyConfig) -> Self {
+        Self::with_config(stream.map(map_chars), config)
+    }
+}
+
+impl<Loc, S> Chunked<MapBytes<Loc, S>>
+where S: Iterator<Item = (Loc, u8)>
+    {
+    /// Parses `(Loc, u8)` pairs (e.g. `bytes.iter().cloned().enumerate()`), reporting non-ASCII bytes as a `SourceError`
+    pub fn from_bytes(stream: S) -> Self {
+        Self::new(stream.map(map_bytes as fn(_) -> _))
+    }
+
+    pub fn from_bytes_with_config(stream: S, config: BodyConfig) -> Self {
+        Self::with_config(stream.map(map_bytes as fn(_) -> _), config)
+    }
 }
 
 impl<'a, Loc, E, S> Iterator for Chunked<S>
@@ -329,7 +623,7 @@ where Loc: 'a,
     /// May panic if called after it returns `None`
     fn next(&mut self) -> Option<Result<Bytes, BodyError<Loc, E>>> {
         self.state.take().and_then(|state| {
-            let (state, result) = state.process(&mut self.stream);
+            let (state, result) = state.process(&mut self.stream, &mut self.sextets);
             self.state = state;
             result
         })
@@ -355,12 +649,12 @@ fn three(a: u8, b: u8, c: u8) -> ChunkedState {
 }
 
 impl ChunkedState {
-    fn process<'a, Loc: 'a, E: 'a>(self, stream: &'a mut Iterator<Item = Result<(Loc, char), E>>) -> (Option<Self>, Option<Result<Bytes, BodyError<Loc, E>>>) {
+    fn process<'a, Loc: 'a, E: 'a>(self, stream: &'a mut Iterator<Item = Result<(Loc, char), E>>, sextets: &mut Sextets) -> (Option<Self>, Option<Result<Bytes, BodyError<Loc, E>>>) {
         use self::ChunkedState::*;
         use self::ChunkedState2::*;
         
         let v = match self {
-            Zero => match get_6_bits(stream) {
+            Zero => match sextets.next(stream, 0, 0) {
                 Err(e) => return (Some(Zero), Some(Err(e))),
                 Ok(None) => return (None, None),
                 Ok(Some(v)) => One(v << 2),
@@ -368,45 +662,45 @@ impl ChunkedState {
             NonZero(v) => v,
         };
 
-        v.process(stream)
+        v.process(stream, sextets)
     }
 }
 
 impl ChunkedState2 {
-    fn process<'a, Loc: 'a, E: 'a>(self, stream: &'a mut Iterator<Item = Result<(Loc, char), E>>) -> (Option<ChunkedState>, Option<Result<Bytes, BodyError<Loc, E>>>) {
+    fn process<'a, Loc: 'a, E: 'a>(self, stream: &'a mut Iterator<Item = Result<(Loc, char), E>>, sextets: &mut Sextets) -> (Option<ChunkedState>, Option<Result<Bytes, BodyError<Loc, E>>>) {
         use self::ChunkedState2::*;
         use self::ChunkedState3::*;
         
         let v = match self {
-            One(a) => match get_6_bits(stream) {
+            One(a) => match sextets.next(stream, 1, a) {
                 Err(e) => return (Some(one(a)), Some(Err(e))),
-                Ok(None) => return (None, Some(Ok(Bytes::One([a])))),
+                Ok(None) => return (None, None),
                 Ok(Some(v)) => Two(a | (v >> 4), (v & 0b1111) << 4),
             },
             NonOne(v) => v,
         };
 
-        v.process(stream)
+        v.process(stream, sextets)
     }
 }
 
 impl ChunkedState3 {
-    fn process<'a, Loc: 'a, E: 'a>(self, stream: &'a mut Iterator<Item = Result<(Loc, char), E>>) -> (Option<ChunkedState>, Option<Result<Bytes, BodyError<Loc, E>>>) {
+    fn process<'a, Loc: 'a, E: 'a>(self, stream: &'a mut Iterator<Item = Result<(Loc, char), E>>, sextets: &mut Sextets) -> (Option<ChunkedState>, Option<Result<Bytes, BodyError<Loc, E>>>) {
         use self::ChunkedState::*;
         use self::ChunkedState3::*;
         
         let (a, b, c) = match self {
-            Two(a, b) => match get_6_bits(stream) {
+            Two(a, b) => match sextets.next(stream, 2, b) {
                 Err(e) => return (Some(two(a, b)), Some(Err(e))),
-                Ok(None) => return (None, Some(Ok(Bytes::Two([a, b])))),
+                Ok(None) => return (None, Some(Ok(Bytes::One([a])))),
                 Ok(Some(v)) => (a, b | (v >> 2), (v & 0b11) << 6),
             },
             Three(a, b, c) => (a, b, c),
         };
 
-        match get_6_bits(stream) {
+        match sextets.next(stream, 3, c) {
             Err(e) => (Some(three(a, b, c)), Some(Err(e))),
-            Ok(None) => (None, Some(Ok(Bytes::Three([a, b, c])))),
+            Ok(None) => (None, Some(Ok(Bytes::Two([a, b])))),
             Ok(Some(v)) => (Some(Zero), Some(Ok(Bytes::Three([a, b, c | v])))),
         }
     }
@@ -414,15 +708,16 @@ impl ChunkedState3 {
 
 pub struct Single<S> {
     stream: S,
+    sextets: Sextets,
     state: Option<SingleState>,
 }
 
-enum SingleState {
+pub(crate) enum SingleState {
     ZeroBits,
     NonZeroBits(SingleState2),
 }
 
-enum SingleState2 {
+pub(crate) enum SingleState2 {
     SixBits(u8),
     FourBits(u8),
     TwoBits(u8),
@@ -432,8 +727,12 @@ impl<Loc, E, S> Single<S>
 where S: Iterator<Item = Result<(Loc, char), E>>
 {
     pub fn new(stream: S) -> Self {
+        Self::with_config(stream, BodyConfig::default())
+    }
+
+    pub fn with_config(stream: S, config: BodyConfig) -> Self {
         Single{
-            stream, state: Some(SingleState::ZeroBits)
+            stream, sextets: Sextets::new(config), state: Some(SingleState::ZeroBits)
         }
     }
 }
@@ -444,6 +743,23 @@ where S: Iterator<Item = (Loc, char)>
     pub fn from_chars(stream: S) -> Self {
         Self::new(stream.map(map_chars))
     }
+
+    pub fn from_chars_with_config(stream: S, config: BodyConfig) -> Self {
+        Self::with_config(stream.map(map_chars), config)
+    }
+}
+
+impl<Loc, S> Single<MapBytes<Loc, S>>
+where S: Iterator<Item = (Loc, u8)>
+    {
+    /// Parses `(Loc, u8)` pairs (e.g. `bytes.iter().cloned().enumerate()`), reporting non-ASCII bytes as a `SourceError`
+    pub fn from_bytes(stream: S) -> Self {
+        Self::new(stream.map(map_bytes as fn(_) -> _))
+    }
+
+    pub fn from_bytes_with_config(stream: S, config: BodyConfig) -> Self {
+        Self::with_config(stream.map(map_bytes as fn(_) -> _), config)
+    }
 }
 
 impl<'a, Loc, E, S> Iterator for Single<S>
@@ -456,29 +772,42 @@ where Loc: 'a,
     /// May panic if called after it returns `None`
     fn next(&mut self) -> Option<Result<u8, BodyError<Loc, E>>> {
         self.state.take().and_then(|state| {
-            let (state, result) = state.process(&mut self.stream);
+            let (state, result) = state.process(&mut self.stream, &mut self.sextets);
             self.state = state;
             result
         })
     }
 }
 
+impl SingleState2 {
+    /// The number of characters already read in the current quantum, and the bits not yet output
+    fn position(&self) -> (u8, u8) {
+        use self::SingleState2::*;
+        match *self {
+            SixBits(v) => (1, v),
+            FourBits(v) => (2, v),
+            TwoBits(v) => (3, v),
+        }
+    }
+}
+
 impl SingleState {
-    fn process<'a, Loc: 'a, E: 'a>(self, stream: &'a mut Iterator<Item = Result<(Loc, char), E>>) -> (Option<SingleState>, Option<Result<u8, BodyError<Loc, E>>>) {
+    pub(crate) fn process<'a, Loc: 'a, E: 'a>(self, stream: &'a mut Iterator<Item = Result<(Loc, char), E>>, sextets: &mut Sextets) -> (Option<SingleState>, Option<Result<u8, BodyError<Loc, E>>>) {
         use self::SingleState::*;
         use self::SingleState2::*;
         
         let v = if let NonZeroBits(v) = self {
             v
         } else {
-            match get_6_bits(stream) {
+            match sextets.next(stream, 0, 0) {
                 Err(e) => return (Some(ZeroBits), Some(Err(e))),
                 Ok(None) => return (None, None),
                 Ok(Some(v)) => SixBits(v << 2),
             }
         };
 
-        let new = match get_6_bits(stream) {
+        let (position, leftover) = v.position();
+        let new = match sextets.next(stream, position, leftover) {
             Err(e) => return (Some(NonZeroBits(v)), Some(Err(e))),
             Ok(None) => return (None, None),
             Ok(Some(v)) => v,
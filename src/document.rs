@@ -0,0 +1,166 @@
+use core::iter::{FromIterator, Map};
+use core::marker::PhantomData;
+use core::mem::replace;
+
+use boundary::{BoundaryType, BoundaryParser, EncapsulationBoundaryError};
+use body::{BodyConfig, Bytes, Chunked};
+use error::{PemError, MatchBegin};
+use {Void, map_chars};
+
+/// A single decoded PEM block
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Document<Lbl, B> {
+    pub label: Lbl,
+    pub body: B,
+}
+
+/// Controls how `Documents` parses each block
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DocumentsConfig {
+    pub body: BodyConfig,
+    /// After an error, skip to the next line starting with `-----BEGIN ` and carry on, rather than stopping
+    pub recover: bool,
+}
+
+/// Iterates over each PEM block in a stream of concatenated blocks (e.g. a CA bundle).
+///
+/// The label is accumulated into `Lbl` and the body is collected into `B`.
+/// The `END` boundary must have the same label as the `BEGIN` boundary.
+/// Explanatory text before and between blocks is skipped.
+/// Stops after the first error, unless `DocumentsConfig::recover` is set.
+pub struct Documents<S, Lbl, B> {
+    stream: Lines<S>,
+    config: DocumentsConfig,
+    /// Set after an error, until the rest of the bad block has been skipped
+    resync: bool,
+    /// How much of `-----BEGIN ` the failed block read from the start of the current line
+    begin_matched: usize,
+    done: bool,
+    marker: PhantomData<fn() -> (Lbl, B)>,
+}
+
+/// Keeps track of the position within the current line
+struct Lines<S> {
+    stream: S,
+    /// The number of characters read from the current line, so `0` at the start of a line
+    column: usize,
+}
+
+impl<Loc, E, S> Iterator for Lines<S>
+where S: Iterator<Item = Result<(Loc, char), E>>
+{
+    type Item = Result<(Loc, char), E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let c = self.stream.next();
+        if let Some(Ok((_, c))) = c {
+            self.column = if c == '\n' || c == '\r' { 0 } else { self.column + 1 };
+        }
+        c
+    }
+}
+
+impl<Loc, E, S, Lbl, B> Documents<S, Lbl, B>
+where S: Iterator<Item = Result<(Loc, char), E>>
+{
+    pub fn new(stream: S) -> Self {
+        Self::with_config(stream, DocumentsConfig::default())
+    }
+
+    pub fn with_config(stream: S, config: DocumentsConfig) -> Self {
+        Documents{
+            stream: Lines{stream, column: 0}, config, resync: false, begin_matched: 0, done: false, marker: PhantomData,
+        }
+    }
+}
+
+impl<Loc, S, Lbl, B> Documents<Map<S, fn((Loc, char)) -> Result<(Loc, char), Void>>, Lbl, B>
+where S: Iterator<Item = (Loc, char)>
+    {
+    pub fn from_chars(stream: S) -> Self {
+        Self::new(stream.map(map_chars))
+    }
+
+    pub fn from_chars_with_config(stream: S, config: DocumentsConfig) -> Self {
+        Self::with_config(stream.map(map_chars), config)
+    }
+}
+
+impl<Loc, E, S, Lbl, B> Documents<S, Lbl, B>
+where S: Iterator<Item = Result<(Loc, char), E>>,
+    Lbl: Default + Extend<char> + AsRef<str>,
+    B: FromIterator<Bytes>,
+{
+    /// Returns `Ok(None)` if there are no more blocks
+    fn parse(&mut self) -> Result<Option<Document<Lbl, B>>, PemError<Loc, E>> {
+        let begin_matched = replace(&mut self.begin_matched, 0);
+        // A BEGIN boundary must start a line, so skip what's left of the line the error was on, unless it's a BEGIN boundary
+        while self.resync && begin_matched == 0 && self.stream.column != 0 {
+            match self.stream.next() {
+                Some(Err(e)) => return Err(PemError::SourceError(e)),
+                None => return Ok(None),
+                Some(Ok(_)) => {},
+            }
+        }
+        self.resync = false;
+
+        let mut label = Lbl::default();
+        {
+            let mut parser = if begin_matched != 0 {
+                BoundaryParser::resume_begin(&mut self.stream, &mut label, begin_matched)
+            } else {
+                BoundaryParser::new(BoundaryType::SkipToBegin, &mut self.stream, &mut label)
+            };
+            if let Some(e) = parser.next() {
+                return Err(PemError::SourceError(e));
+            }
+            match parser.complete() {
+                Ok(()) => {},
+                Err(EncapsulationBoundaryError::MissingBoundary) => return Ok(None),
+                Err(e) => return Err(PemError::begin(e)),
+            }
+        }
+
+        let body: Result<B, _> = Chunked::with_config(&mut self.stream, self.config.body).collect();
+        let body = body?;
+
+        {
+            let mut parser = BoundaryParser::new(BoundaryType::End, &mut self.stream, MatchBegin(label.as_ref().chars()));
+            if let Some(e) = parser.next() {
+                return Err(PemError::SourceError(e));
+            }
+            let result = parser.complete();
+            // Without an END boundary, the body runs into the next BEGIN boundary, which has been read as far as `-----B`
+            if let Err(EncapsulationBoundaryError::Mismatch{expected: 'E', found: 'B', ..}) = result {
+                if self.stream.column == "-----B".len() {
+                    self.begin_matched = self.stream.column;
+                }
+            }
+            result.map_err(PemError::end_mismatch)?;
+        }
+
+        Ok(Some(Document{label, body}))
+    }
+}
+
+impl<Loc, E, S, Lbl, B> Iterator for Documents<S, Lbl, B>
+where S: Iterator<Item = Result<(Loc, char), E>>,
+    Lbl: Default + Extend<char> + AsRef<str>,
+    B: FromIterator<Bytes>,
+{
+    type Item = Result<Document<Lbl, B>, PemError<Loc, E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.parse();
+        match result {
+            Ok(Some(_)) => {},
+            Err(_) if self.config.recover => self.resync = true,
+            Ok(None) | Err(_) => self.done = true,
+        }
+        result.transpose()
+    }
+}
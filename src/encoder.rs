@@ -0,0 +1,262 @@
+use core::str::Chars;
+use core::iter::Flatten;
+
+use body::Bytes;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes up to 3 bytes as 4 characters, padding with `'='` as needed.
+///
+/// Panics if `input` is empty or longer than 3 bytes.
+pub(crate) fn encode_quantum(input: &[u8]) -> [char; 4] {
+    assert!(!input.is_empty() && input.len() <= 3);
+
+    let a = input[0];
+    let b = input.get(1).cloned().unwrap_or(0);
+    let c = input.get(2).cloned().unwrap_or(0);
+
+    let sextet = |v: u8| ALPHABET[v as usize] as char;
+    let mut out = [
+        sextet(a >> 2),
+        sextet(((a & 0b11) << 4) | (b >> 4)),
+        sextet(((b & 0b1111) << 2) | (c >> 6)),
+        sextet(c & 0b111111),
+    ];
+    for o in out.iter_mut().skip(input.len() + 1) {
+        *o = '=';
+    }
+    out
+}
+
+/// Which characters end a line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `"\n"`, as used by RFC 7468
+    Lf,
+    /// `"\r\n"`, as expected by some legacy consumers
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Controls the layout of encoded output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncoderConfig {
+    /// The number of characters in each line of the body. `0` disables wrapping.
+    pub line_length: usize,
+    pub line_ending: LineEnding,
+    /// Whether a line ending follows the `END` boundary
+    pub trailing_newline: bool,
+}
+
+impl Default for EncoderConfig {
+    /// 64 characters per line, `"\n"` line endings, and a trailing newline, as in RFC 7468 `stricttextualmsg`
+    fn default() -> Self {
+        EncoderConfig{
+            line_length: 64,
+            line_ending: LineEnding::Lf,
+            trailing_newline: true,
+        }
+    }
+}
+
+/// Encodes a stream of bytes as a complete PEM document.
+///
+/// Yields the characters of the `BEGIN` boundary, the wrapped body, and the `END` boundary.
+pub struct Encoder<L, S> {
+    stream: S,
+    label: L,
+    config: EncoderConfig,
+    pending: Chars<'static>,
+    newline: Chars<'static>,
+    state: EncoderState<L>,
+    column: usize,
+}
+
+enum EncoderState<L> {
+    BeginLabel(L),
+    Body{
+        quantum: [char; 4],
+        next: usize,
+        last: bool,
+    },
+    EndLabel(L),
+    Done,
+}
+
+impl<L, S> Encoder<L, S>
+where L: Iterator<Item = char> + Clone,
+    S: Iterator<Item = u8>
+{
+    pub fn new(stream: S, label: L) -> Self {
+        Self::with_config(stream, label, EncoderConfig::default())
+    }
+
+    pub fn with_config(stream: S, label: L, config: EncoderConfig) -> Self {
+        Encoder{
+            stream,
+            state: EncoderState::BeginLabel(label.clone()),
+            label,
+            config,
+            pending: "-----BEGIN ".chars(),
+            newline: "".chars(),
+            column: 0,
+        }
+    }
+}
+
+impl<L, S> Encoder<L, Flatten<S>>
+where L: Iterator<Item = char> + Clone,
+    S: Iterator<Item = Bytes>
+{
+    /// Encodes the chunks produced by `body::Chunked`.
+    ///
+    /// `Chunked` yields `Result`s, so its errors must be handled first (e.g. by collecting into a `Result<Vec<Bytes>, _>`).
+    pub fn from_chunks(stream: S, label: L) -> Self {
+        Self::new(stream.flatten(), label)
+    }
+
+    pub fn from_chunks_with_config(stream: S, label: L, config: EncoderConfig) -> Self {
+        Self::with_config(stream.flatten(), label, config)
+    }
+}
+
+impl<L, S> Iterator for Encoder<L, S>
+where L: Iterator<Item = char> + Clone,
+    S: Iterator<Item = u8>
+{
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        use self::EncoderState::*;
+
+        loop {
+            if let Some(c) = self.pending.next() {
+                return Some(c);
+            }
+            if let Some(c) = self.newline.next() {
+                return Some(c);
+            }
+
+            match self.state {
+                BeginLabel(ref mut label) => if let Some(c) = label.next() {
+                    return Some(c);
+                } else {
+                    self.pending = "-----".chars();
+                    self.newline = self.config.line_ending.as_str().chars();
+                    self.state = Body{quantum: ['='; 4], next: 4, last: false};
+                },
+                Body{ref mut quantum, ref mut next, last} => {
+                    if *next < 4 {
+                        if self.column == self.config.line_length && self.column != 0 {
+                            self.column = 0;
+                            self.newline = self.config.line_ending.as_str().chars();
+                            continue;
+                        }
+                        let c = quantum[*next];
+                        self.column += 1;
+                        *next += 1;
+                        return Some(c);
+                    }
+
+                    let mut input = [0; 3];
+                    let mut len = 0;
+                    if !last {
+                        while len < 3 {
+                            match self.stream.next() {
+                                Some(b) => input[len] = b,
+                                None => break,
+                            }
+                            len += 1;
+                        }
+                    }
+
+                    if len == 0 {
+                        // Finish the last line before starting the END boundary
+                        if self.column != 0 {
+                            self.column = 0;
+                            self.newline = self.config.line_ending.as_str().chars();
+                            self.state = Body{quantum: ['='; 4], next: 4, last: true};
+                            continue;
+                        }
+                        self.pending = "-----END ".chars();
+                        self.state = EndLabel(self.label.clone());
+                        continue;
+                    }
+
+                    self.state = Body{quantum: encode_quantum(&input[..len]), next: 0, last: len < 3};
+                },
+                EndLabel(ref mut label) => if let Some(c) = label.next() {
+                    return Some(c);
+                } else {
+                    self.pending = "-----".chars();
+                    if self.config.trailing_newline {
+                        self.newline = self.config.line_ending.as_str().chars();
+                    }
+                    self.state = Done;
+                },
+                Done => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Encoder, EncoderConfig, LineEnding};
+
+    #[test]
+    fn test_encode() {
+        fn helper(input: &[u8], expected: &str) {
+            let mut encoded = Encoder::new(input.iter().cloned(), "DATA".chars());
+            assert!(encoded.by_ref().eq(expected.chars()));
+            assert_eq!(encoded.next(), None);
+        }
+
+        helper(b"", "-----BEGIN DATA-----\n-----END DATA-----\n");
+        helper(b"f", "-----BEGIN DATA-----\nZg==\n-----END DATA-----\n");
+        helper(b"fo", "-----BEGIN DATA-----\nZm8=\n-----END DATA-----\n");
+        helper(b"foo", "-----BEGIN DATA-----\nZm9v\n-----END DATA-----\n");
+        helper(b"foobar", "-----BEGIN DATA-----\nZm9vYmFy\n-----END DATA-----\n");
+        helper(&[0xff; 48], concat!(
+            "-----BEGIN DATA-----\n",
+            "////////////////////////////////////////////////////////////////\n",
+            "-----END DATA-----\n",
+        ));
+        helper(&[0xff; 49], concat!(
+            "-----BEGIN DATA-----\n",
+            "////////////////////////////////////////////////////////////////\n",
+            "/w==\n",
+            "-----END DATA-----\n",
+        ));
+    }
+
+    #[test]
+    fn test_encode_config() {
+        fn helper(input: &[u8], config: EncoderConfig, expected: &str) {
+            let encoded = Encoder::with_config(input.iter().cloned(), "DATA".chars(), config);
+            assert!(encoded.eq(expected.chars()));
+        }
+
+        let crlf = EncoderConfig{line_ending: LineEnding::CrLf, ..EncoderConfig::default()};
+        helper(b"", crlf, "-----BEGIN DATA-----\r\n-----END DATA-----\r\n");
+        helper(b"foobar", crlf, "-----BEGIN DATA-----\r\nZm9vYmFy\r\n-----END DATA-----\r\n");
+
+        let narrow = EncoderConfig{line_length: 6, trailing_newline: false, ..EncoderConfig::default()};
+        helper(b"foobar", narrow, "-----BEGIN DATA-----\nZm9vYm\nFy\n-----END DATA-----");
+
+        let unwrapped = EncoderConfig{line_length: 0, ..EncoderConfig::default()};
+        helper(&[0xff; 51], unwrapped, concat!(
+            "-----BEGIN DATA-----\n",
+            "////////////////////////////////////////////////////////////////////\n",
+            "-----END DATA-----\n",
+        ));
+    }
+}
@@ -0,0 +1,116 @@
+use core::fmt;
+
+use boundary::{EncapsulationBoundaryError, Label};
+use body::BodyError;
+use header::EncapsulatedHeaderError;
+use Void;
+
+/// An error from any stage of parsing a PEM block
+#[derive(Debug, PartialEq)]
+pub enum PemError<Loc, E, LabelError = Void, HeaderError = Void> {
+    SourceError(E),
+    BeginBoundary(EncapsulationBoundaryError<Loc, LabelError>),
+    Headers(EncapsulatedHeaderError<Loc, HeaderError>),
+    Body(BodyError<Loc, E>),
+    EndBoundary(EncapsulationBoundaryError<Loc, LabelError>),
+    /// The label of the `END` boundary differs from the `BEGIN` boundary, starting at `location`
+    LabelMismatch{
+        location: Loc,
+    },
+}
+
+impl<Loc, E, LabelError, HeaderError> PemError<Loc, E, LabelError, HeaderError> {
+    /// Tags an error from the `BEGIN` boundary
+    pub fn begin(e: EncapsulationBoundaryError<Loc, LabelError>) -> Self {
+        PemError::BeginBoundary(e)
+    }
+
+    /// Tags an error from the `END` boundary
+    pub fn end(e: EncapsulationBoundaryError<Loc, LabelError>) -> Self {
+        PemError::EndBoundary(e)
+    }
+
+    /// Converts an error from an `END` boundary parsed with `MatchBegin`
+    pub(crate) fn end_mismatch(e: EncapsulationBoundaryError<Loc, Mismatched>) -> Self {
+        use boundary::EncapsulationBoundaryError::*;
+        PemError::EndBoundary(match e {
+            LabelError{location, ..} => return PemError::LabelMismatch{location},
+            MissingExpected(c) => MissingExpected(c),
+            Mismatch{location, expected, found} => Mismatch{location, expected, found},
+            MissingBoundary => MissingBoundary,
+        })
+    }
+}
+
+impl<Loc, E, LabelError, HeaderError> From<BodyError<Loc, E>> for PemError<Loc, E, LabelError, HeaderError> {
+    fn from(e: BodyError<Loc, E>) -> Self {
+        match e {
+            BodyError::SourceError(e) => PemError::SourceError(e),
+            e => PemError::Body(e),
+        }
+    }
+}
+
+impl<Loc, E, LabelError, HeaderError> From<EncapsulatedHeaderError<Loc, HeaderError>> for PemError<Loc, E, LabelError, HeaderError> {
+    fn from(e: EncapsulatedHeaderError<Loc, HeaderError>) -> Self {
+        PemError::Headers(e)
+    }
+}
+
+impl<Loc: fmt::Display, E, LabelError, HeaderError> fmt::Display for PemError<Loc, E, LabelError, HeaderError> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::PemError::*;
+        match *self {
+            SourceError(_) => f.write_str("error reading the input"),
+            BeginBoundary(ref e) => write!(f, "invalid BEGIN boundary: {}", e),
+            Headers(ref e) => write!(f, "invalid headers: {}", e),
+            Body(ref e) => write!(f, "invalid body: {}", e),
+            EndBoundary(ref e) => write!(f, "invalid END boundary: {}", e),
+            LabelMismatch{ref location} => write!(f, "END label doesn't match the BEGIN label at {}", location),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Loc, E, LabelError, HeaderError> ::std::error::Error for PemError<Loc, E, LabelError, HeaderError>
+where Loc: fmt::Debug + fmt::Display,
+    E: ::std::error::Error + 'static,
+    LabelError: ::std::error::Error + 'static,
+    HeaderError: ::std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn (::std::error::Error) + 'static)> {
+        use self::PemError::*;
+        match *self {
+            SourceError(ref e) => Some(e),
+            BeginBoundary(ref e) | EndBoundary(ref e) => e.source(),
+            Headers(ref e) => e.source(),
+            Body(ref e) => e.source(),
+            LabelMismatch{..} => None,
+        }
+    }
+}
+
+/// The `END` label differs from the `BEGIN` label
+#[derive(Debug)]
+pub(crate) struct Mismatched;
+
+/// Matches the `END` label against the characters of the `BEGIN` label, failing with `Mismatched`
+pub(crate) struct MatchBegin<I>(pub(crate) I);
+
+impl<I: Iterator<Item = char>> Label for MatchBegin<I> {
+    type LabelError = Mismatched;
+
+    fn push(&mut self, found: char) -> Result<Option<char>, Mismatched> {
+        match self.0.next() {
+            Some(expected) if expected == found => Ok(None),
+            _ => Err(Mismatched),
+        }
+    }
+
+    fn complete(&mut self) -> Result<Option<char>, Mismatched> {
+        match self.0.next() {
+            None => Ok(None),
+            Some(_) => Err(Mismatched),
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::VecDeque;
+use std::io;
+use std::mem::replace;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use futures_io::AsyncRead;
+
+use body::BodyConfig;
+use document::Document;
+use push::{Event, PushParser};
+
+/// The size of the buffer each read fills
+const BUFFER_SIZE: usize = 4096;
+
+/// Decodes each PEM block read from an `AsyncRead`, as a `Stream` of `Document`s.
+///
+/// Parsing is done by a `PushParser`, so a read returning `Pending` never loses progress.
+/// Explanatory text before and between blocks is skipped.
+/// Errors are reported as `io::Error`s. Parsing errors carry a `push::PushError`, and end the stream.
+/// Errors from the reader don't, so polling again retries the read and carries on where it left off.
+pub struct DocumentStream<R> {
+    inner: R,
+    /// `None` once the input has ended or failed
+    parser: Option<PushParser>,
+    buffer: Box<[u8]>,
+    /// The block being decoded
+    document: Document<String, Vec<u8>>,
+    /// Results not yet returned
+    ready: VecDeque<io::Result<Document<String, Vec<u8>>>>,
+}
+
+impl<R: AsyncRead + Unpin> DocumentStream<R> {
+    /// Parses the body with the default `BodyConfig`
+    pub fn new(inner: R) -> Self {
+        Self::with_config(inner, BodyConfig::default())
+    }
+
+    /// Parses the body with the given `BodyConfig`
+    pub fn with_config(inner: R, config: BodyConfig) -> Self {
+        DocumentStream{
+            inner,
+            parser: Some(PushParser::with_config(config)),
+            buffer: vec![0; BUFFER_SIZE].into_boxed_slice(),
+            document: Document{label: String::new(), body: Vec::new()},
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Returns the reader, discarding anything read but not yet returned
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for DocumentStream<R> {
+    type Item = io::Result<Document<String, Vec<u8>>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(result) = this.ready.pop_front() {
+                return Poll::Ready(Some(result));
+            }
+            let mut parser = match this.parser.take() {
+                Some(parser) => parser,
+                None => return Poll::Ready(None),
+            };
+
+            let len = match Pin::new(&mut this.inner).poll_read(cx, &mut this.buffer) {
+                Poll::Ready(Ok(len)) => len,
+                read => {
+                    // Keep the progress so far, for the next poll
+                    this.parser = Some(parser);
+                    match read {
+                        Poll::Ready(Err(ref e)) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+                        _ => return Poll::Pending,
+                    }
+                },
+            };
+
+            let document = &mut this.document;
+            let ready = &mut this.ready;
+            let mut failed = false;
+            let mut sink = |event: Event| match event {
+                Event::BeginLabel(label) => document.label.push_str(label),
+                Event::BodyBytes(bytes) => document.body.extend_from_slice(bytes),
+                Event::End => {
+                    let empty = Document{label: String::new(), body: Vec::new()};
+                    ready.push_back(Ok(replace(document, empty)));
+                },
+                Event::Error(e) => {
+                    failed = true;
+                    ready.push_back(Err(e.into()));
+                },
+            };
+
+            if len == 0 {
+                parser.finish(&mut sink);
+            } else {
+                parser.feed(&this.buffer[..len], &mut sink);
+                if !failed {
+                    this.parser = Some(parser);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    use futures_core::Stream;
+    use futures_io::AsyncRead;
+
+    use body::BodyError;
+    use document::Document;
+    use push::PushError;
+    use super::DocumentStream;
+
+    /// Reads a few bytes at a time, returning `Pending` at pseudo-random points
+    struct Trickle<'a> {
+        data: &'a [u8],
+        seed: u32,
+    }
+
+    impl<'a> Trickle<'a> {
+        fn random(&mut self) -> usize {
+            // xorshift
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 17;
+            self.seed ^= self.seed << 5;
+            self.seed as usize
+        }
+    }
+
+    impl<'a> AsyncRead for Trickle<'a> {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            if self.random() % 3 == 0 {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            let len = (self.random() % 7 + 1).min(buf.len()).min(self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Poll::Ready(Ok(len))
+        }
+    }
+
+    /// Fails with `TimedOut` before each successful read
+    struct Flaky<'a> {
+        data: &'a [u8],
+        failed: bool,
+    }
+
+    impl<'a> AsyncRead for Flaky<'a> {
+        fn poll_read(mut self: Pin<&mut Self>, _: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            self.failed = !self.failed;
+            if self.failed {
+                return Poll::Ready(Err(io::ErrorKind::TimedOut.into()));
+            }
+
+            let len = buf.len().min(self.data.len()).min(5);
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Poll::Ready(Ok(len))
+        }
+    }
+
+    /// Does nothing when woken, since the tests poll in a loop
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn collect(input: &str, seed: u32) -> Vec<Result<Document<String, Vec<u8>>, PushError>> {
+        let mut stream = DocumentStream::new(Trickle{data: input.as_bytes(), seed});
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        let mut results = Vec::new();
+        loop {
+            match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Pending => {},
+                Poll::Ready(None) => return results,
+                Poll::Ready(Some(result)) => results.push(result.map_err(|e| {
+                    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+                    *e.into_inner().unwrap().downcast::<PushError>().unwrap()
+                })),
+            }
+        }
+    }
+
+    #[test]
+    fn test_document_stream() {
+        fn document(label: &str, body: &[u8]) -> Result<Document<String, Vec<u8>>, PushError> {
+            Ok(Document{label: label.to_owned(), body: body.to_vec()})
+        }
+
+        let input = "Text\n-----BEGIN A-----\nZm9v\nYmFy\n-----END A-----\n-----BEGIN B-----\n-----END B-----\n";
+        let invalid = "-----BEGIN A-----\nZg==\n-----END A-----\n-----BEGIN B-----\nZ!==\n-----END B-----\n-----BEGIN C-----\n-----END C-----\n";
+        for seed in 1..50 {
+            assert_eq!(collect(input, seed), vec![document("A", b"foobar"), document("B", b"")]);
+            assert_eq!(collect(invalid, seed), vec![
+                document("A", b"f"),
+                Err(PushError::Body(BodyError::InvalidCharacter{location: 58, found: '!'})),
+            ]);
+            assert_eq!(collect("", seed), vec![]);
+        }
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let input = "-----BEGIN A-----\nZm9vYmFy\n-----END A-----\n-----BEGIN B-----\nYg==\n-----END B-----\n";
+        let mut stream = DocumentStream::new(Flaky{data: input.as_bytes(), failed: false});
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+
+        // Each read error is reported, and the next poll picks up where the last one left off
+        let mut documents = Vec::new();
+        let mut errors = 0;
+        loop {
+            match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Pending => panic!("Flaky is never pending"),
+                Poll::Ready(None) => break,
+                Poll::Ready(Some(Ok(document))) => documents.push(document),
+                Poll::Ready(Some(Err(e))) => {
+                    assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+                    errors += 1;
+                },
+            }
+        }
+        assert_eq!(documents, vec![
+            Document{label: "A".to_owned(), body: b"foobar".to_vec()},
+            Document{label: "B".to_owned(), body: b"b".to_vec()},
+        ]);
+        // One before each read of up to 5 bytes, and one before reading the end
+        assert_eq!(errors, (input.len() + 4) / 5 + 1);
+    }
+}
@@ -1,14 +1,54 @@
-use core::ops::{Generator};
+use boundary::{BoundaryType, BoundaryParserState, EncapsulationBoundaryError, Label};
+use body::{BodyError, Bytes, Chunked, Single};
+use {MapChars, map_chars};
+
+/// The result of resuming a `Generator`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GeneratorState<Y, R> {
+    /// The generator suspended with a value, and may be resumed again
+    Yielded(Y),
+    /// The generator finished with a value
+    Complete(R),
+}
 
-use boundary::{BoundaryType, EncapsulationBoundaryError, Label};
-use body::{BodyError, Bytes, get_6_bits};
-use {Void, map_chars, is_whitespace};
+/// A resumable computation, which yields stream errors (or output) and returns its final result.
+///
+/// A stand-in for `core::ops::Generator`, implemented by explicit state machines.
+pub trait Generator {
+    type Yield;
+    type Return;
 
+    /// Runs until the next yield or the end. Panics if called after returning `Complete`.
+    fn resume(&mut self) -> GeneratorState<Self::Yield, Self::Return>;
+}
 
-pub fn parse_boundary_chars<Loc, Lbl, S>(b: BoundaryType, stream: S, label: Lbl) -> impl Generator<
-    Yield=Void,
-    Return=Result<(), EncapsulationBoundaryError<Loc, Lbl::LabelError>>,
->
+/// The generator returned by `parse_boundary`
+pub struct BoundaryGenerator<Loc, Lbl, S> {
+    stream: S,
+    state: Option<BoundaryParserState<Loc, Lbl>>,
+}
+
+impl<Loc, Lbl, E, S> Generator for BoundaryGenerator<Loc, Lbl, S>
+where Lbl: Label,
+    S: Iterator<Item = Result<(Loc, char), E>>
+{
+    type Yield = E;
+    type Return = Result<(), EncapsulationBoundaryError<Loc, Lbl::LabelError>>;
+
+    fn resume(&mut self) -> GeneratorState<E, Self::Return> {
+        let state = self.state.take().expect("generator resumed after completion");
+        match state.process(&mut self.stream, &mut 0) {
+            Ok(Some((state, e))) => {
+                self.state = Some(state);
+                GeneratorState::Yielded(e)
+            },
+            Ok(None) => GeneratorState::Complete(Ok(())),
+            Err(e) => GeneratorState::Complete(Err(e)),
+        }
+    }
+}
+
+pub fn parse_boundary_chars<Loc, Lbl, S>(b: BoundaryType, stream: S, label: Lbl) -> BoundaryGenerator<Loc, Lbl, MapChars<Loc, S>>
 where Lbl: Label,
     S: Iterator<Item = (Loc, char)> {
     parse_boundary(b, stream.map(map_chars), label)
@@ -17,134 +57,32 @@ where Lbl: Label,
 
 /// Parses a boundary and extracts the label.
 ///
-/// To save the `label`, pass `&mut String`, to discard, pass `&mut ()`.
-pub fn parse_boundary<Loc, Lbl, E, S>(b: BoundaryType, mut stream: S, mut label: Lbl) -> impl Generator<
-    Yield=E,
-    Return=Result<(), EncapsulationBoundaryError<Loc, Lbl::LabelError>>,
->
+/// To save the `label`, pass `&mut String`, to discard, pass `DiscardLabel`.
+pub fn parse_boundary<Loc, Lbl, E, S>(b: BoundaryType, stream: S, label: Lbl) -> BoundaryGenerator<Loc, Lbl, S>
 where Lbl: Label,
     S: Iterator<Item = Result<(Loc, char), E>> {
-    move ||{
-        // For BEGIN, eat all the whitespace and the first '-'
-        // END has already had one '-' eaten during body parsing, so don't worry about that
-        let key = match b {
-            BoundaryType::Begin => {
-                loop {
-                    use self::EncapsulationBoundaryError::*;
-                    let v = stream.by_ref()
-                        .skip_while(|c| c.as_ref().ok().map_or(false, is_whitespace)).next();
-                    match v {
-                        Some(Err(e)) => yield e,
-                        None => return Err(MissingExpected('-')),
-                        Some(Ok((location, found))) => if found != '-' {
-                            return Err(Mismatch {expected: '-', found, location})
-                        } else {
-                            break
-                        }
-                    }
-                }
-                "----BEGIN "
-            },
-            BoundaryType::End => "----END ",
-        };
-
-        // Eat the rest of the key
-        for expected in key.chars() {
-            use self::EncapsulationBoundaryError::*;
-            loop {
-                let v = stream.next();
-                match v {
-                    Some(Err(e)) => yield e,
-                    None => return Err(MissingExpected(expected)),
-                    Some(Ok((location, found))) => if expected != found {
-                        return Err(Mismatch {expected, found, location})
-                    } else {
-                        break
-                    }
-                }
-            }
-        }
-
-        // Get the label (this will eat the first two '-'s of the boundary end)
-        let mut prev_dash = None;
-        loop {
-            use self::EncapsulationBoundaryError::*;
-
-            let v = stream.next();
-            let (location, c) = match v {
-                Some(Err(e)) => {
-                    yield e;
-                    continue
-                },
-                None => return Err(MissingExpected('-')),
-                Some(Ok(c)) => c,
-            };
-
-            // Check for double '-'
-            if c == '-' {
-                if prev_dash.is_none() {
-                    prev_dash = Some(location);
-                    continue;
-                }
-
-                match label.complete() {
-                    Ok(None) => {},
-                    Err(error) => return Err(LabelError{error, location}),
-                    Ok(Some(expected)) => return Err(Mismatch{location, expected, found: c}),
-                }
-
-                break;
-            }
+    BoundaryGenerator{
+        stream, state: Some(BoundaryParserState::new(b, label)),
+    }
+}
 
-            // Add back in any single '-' we skipped over
-            if let Some(prev_location) = prev_dash.take() {
-                match label.push('-') {
-                    Ok(None) => {},
-                    Err(error) => return Err(LabelError{error, location: prev_location}),
-                    Ok(Some(expected)) => return Err(if expected == '-' {
-                        Mismatch{location, expected, found: c}
-                    } else {
-                        Mismatch{location: prev_location, expected, found: '-'}
-                    })
-                }
-            }
+/// The generator returned by `parse_body_chunked`
+pub struct ChunkedGenerator<S>(Chunked<S>);
 
-            match label.push(c) {
-                Ok(None) => {},
-                Err(error) => return Err(LabelError{error, location}),
-                Ok(Some(expected)) => return Err(if expected == c {
-                    Mismatch{location, expected: '-', found: c}
-                } else {
-                    Mismatch{location, expected, found: c}
-                }),
-            }
-        }
+impl<Loc, E, S> Generator for ChunkedGenerator<S>
+where S: Iterator<Item = Result<(Loc, char), E>> {
+    type Yield = Result<Bytes, BodyError<Loc, E>>;
+    type Return = ();
 
-        // Eat the end of the boundary
-        for expected in "---".chars() {
-            use self::EncapsulationBoundaryError::*;
-            loop {
-                let v = stream.next();
-                match v {
-                    Some(Err(e)) => yield e,
-                    None => return Err(MissingExpected(expected)),
-                    Some(Ok((location, found))) => if expected != found {
-                        return Err(Mismatch {expected, found, location})
-                    } else {
-                        break
-                    }
-                }
-            }
+    fn resume(&mut self) -> GeneratorState<Self::Yield, ()> {
+        match self.0.next() {
+            Some(v) => GeneratorState::Yielded(v),
+            None => GeneratorState::Complete(()),
         }
-
-        Ok(())
     }
 }
 
-pub fn parse_body_chunked_chars<Location, S>(stream: S) -> impl Generator<
-    Yield=Result<Bytes, BodyError<Location, Void>>,
-    Return=(),
->
+pub fn parse_body_chunked_chars<Location, S>(stream: S) -> ChunkedGenerator<MapChars<Location, S>>
 where S: Iterator<Item = (Location, char)> {
     parse_body_chunked(stream.map(map_chars))
 }
@@ -152,67 +90,28 @@ where S: Iterator<Item = (Location, char)> {
 /// Parses the body in chunks.
 ///
 /// Stops after consuming a single `-`.
-pub fn parse_body_chunked<Location, E, S>(mut stream: S) -> impl Generator<
-    Yield=Result<Bytes, BodyError<Location, E>>,
-    Return=(),
->
+pub fn parse_body_chunked<Location, E, S>(stream: S) -> ChunkedGenerator<S>
 where S: Iterator<Item = Result<(Location, char), E>> {
-    move ||{
-        loop {
-            let a = loop {
-                let v = get_6_bits(&mut stream);
-                match v {
-                    Err(e) => yield Err(e),
-                    Ok(None) => return,
-                    Ok(Some(v)) => break v << 2,
-                }
-            };
-
-            let (a, b) = loop {
-                let v = get_6_bits(&mut stream);
-                match v {
-                    Err(e) => yield Err(e),
-                    Ok(None) => {
-                        yield Ok(Bytes::One([a]));
-                        return
-                    },
-                    Ok(Some(v)) => break (a | (v >> 4), (v & 0b1111) << 4),
-                }
-            };
-
-            let (b, c) = loop {
-                let v = get_6_bits(&mut stream);
-                match v {
-                    Err(e) => yield Err(e),
-                    Ok(None) => {
-                        yield Ok(Bytes::Two([a, b]));
-                        return
-                    },
-                    Ok(Some(v)) => break (b | (v >> 2), (v & 0b11) << 6),
-                }
-            };
-
-            let c = loop {
-                let v = get_6_bits(&mut stream);
-                match v {
-                    Err(e) => yield Err(e),
-                    Ok(None) => {
-                        yield Ok(Bytes::Three([a, b, c]));
-                        return
-                    },
-                    Ok(Some(v)) => break c | v,
-                }
-            };
+    ChunkedGenerator(Chunked::new(stream))
+}
+
+/// The generator returned by `parse_body_single`
+pub struct SingleGenerator<S>(Single<S>);
+
+impl<Loc, E, S> Generator for SingleGenerator<S>
+where S: Iterator<Item = Result<(Loc, char), E>> {
+    type Yield = Result<u8, BodyError<Loc, E>>;
+    type Return = ();
 
-            yield Ok(Bytes::Three([a, b, c]));
+    fn resume(&mut self) -> GeneratorState<Self::Yield, ()> {
+        match self.0.next() {
+            Some(v) => GeneratorState::Yielded(v),
+            None => GeneratorState::Complete(()),
         }
     }
 }
 
-pub fn parse_body_single_chars<Location, S>(stream: S) -> impl Generator<
-    Yield=Result<u8, BodyError<Location, Void>>,
-    Return=(),
->
+pub fn parse_body_single_chars<Location, S>(stream: S) -> SingleGenerator<MapChars<Location, S>>
 where S: Iterator<Item = (Location, char)> {
     parse_body_single(stream.map(map_chars))
 }
@@ -220,78 +119,19 @@ where S: Iterator<Item = (Location, char)> {
 /// Parses the body one byte of output at a time.
 ///
 /// Stops after consuming a single `-`.
-pub fn parse_body_single<Location, E, S>(mut stream: S) -> impl Generator<
-    Yield=Result<u8, BodyError<Location, E>>,
-    Return=(),
->
+pub fn parse_body_single<Location, E, S>(stream: S) -> SingleGenerator<S>
 where S: Iterator<Item = Result<(Location, char), E>> {
-    move || {
-        loop {
-            let o = loop {
-                let v = get_6_bits(&mut stream);
-                match v {
-                    Err(e) => yield Err(e),
-                    Ok(None) => return,
-                    Ok(Some(v)) => break v << 2,
-                }
-            };
-
-            let o = loop {
-                let v = get_6_bits(&mut stream);
-                match v {
-                    Err(e) => yield Err(e),
-                    Ok(None) => {
-                        yield Ok(o);
-                        return
-                    },
-                    Ok(Some(v)) => {
-                        yield Ok(o | (v >> 4));
-                        break (v & 0b1111) << 4;
-                    },
-                }
-            };
-
-            let o = loop {
-                let v = get_6_bits(&mut stream);
-                match v {
-                    Err(e) => yield Err(e),
-                    Ok(None) => {
-                        yield Ok(o);
-                        return
-                    },
-                    Ok(Some(v)) => {
-                        yield Ok(o | (v >> 2));
-                        break (v & 0b11) << 6;
-                    },
-                }
-            };
-
-            loop {
-                let v = get_6_bits(&mut stream);
-                match v {
-                    Err(e) => yield Err(e),
-                    Ok(None) => {
-                        yield Ok(o);
-                        return
-                    },
-                    Ok(Some(v)) =>{
-                        yield Ok(o | v);
-                        break;
-                    },
-                }
-            }
-        }
-    }
+    SingleGenerator(Single::new(stream))
 }
 
 
 #[cfg(test)]
 mod tests {
-    use core::ops::{Generator, GeneratorState};
-    use boundary::{BoundaryType, LabelMatcher};
+    use boundary::{BoundaryType, LabelMatcher, EncapsulationBoundaryError};
     #[cfg(not(feature = "std"))]
     use boundary::DiscardLabel;
-    use super::{parse_boundary_chars};
+    use body::{BodyError, Bytes};
+    use super::{Generator, GeneratorState, parse_boundary, parse_boundary_chars, parse_body_chunked, parse_body_single};
 
 
     #[test]
@@ -371,4 +211,34 @@ mod tests {
         helper(BoundaryType::End, END_PRIVATE, "RSA PRIVATE KEY");
         helper(BoundaryType::End, END_COMPLEX, "\u{211D}-\u{212D}");
     }
+
+    #[test]
+    fn test_stream_errors() {
+        fn stream(input: &str) -> impl Iterator<Item = Result<(usize, char), usize>> + '_ {
+            // Every '!' is a stream error
+            input.chars().enumerate().map(|(i, c)| if c == '!' { Err(i) } else { Ok((i, c)) })
+        }
+
+        let mut gen = parse_boundary(BoundaryType::Begin, stream("--!---BEGIN A!B-----"), LabelMatcher("AB".chars()));
+        assert_eq!(gen.resume(), GeneratorState::Yielded(2));
+        assert_eq!(gen.resume(), GeneratorState::Yielded(13));
+        assert_eq!(gen.resume(), GeneratorState::Complete(Ok(())));
+
+        let mut gen = parse_boundary(BoundaryType::End, stream("----END A!C-----"), LabelMatcher("AB".chars()));
+        assert_eq!(gen.resume(), GeneratorState::Yielded(9));
+        assert_eq!(gen.resume(), GeneratorState::Complete(Err(EncapsulationBoundaryError::Mismatch{location: 10, expected: 'B', found: 'C'})));
+
+        let mut gen = parse_body_chunked(stream("QU!JDQ!UI=-"));
+        assert_eq!(gen.resume(), GeneratorState::Yielded(Err(BodyError::SourceError(2))));
+        assert_eq!(gen.resume(), GeneratorState::Yielded(Ok(Bytes::Three(*b"ABC"))));
+        assert_eq!(gen.resume(), GeneratorState::Yielded(Err(BodyError::SourceError(6))));
+        assert_eq!(gen.resume(), GeneratorState::Yielded(Ok(Bytes::Two(*b"AB"))));
+        assert_eq!(gen.resume(), GeneratorState::Complete(()));
+
+        let mut gen = parse_body_single(stream("QU!I=-"));
+        assert_eq!(gen.resume(), GeneratorState::Yielded(Ok(b'A')));
+        assert_eq!(gen.resume(), GeneratorState::Yielded(Err(BodyError::SourceError(2))));
+        assert_eq!(gen.resume(), GeneratorState::Yielded(Ok(b'B')));
+        assert_eq!(gen.resume(), GeneratorState::Complete(()));
+    }
 }
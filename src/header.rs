@@ -0,0 +1,389 @@
+use core::iter::{Chain, Map};
+use core::fmt;
+use core::marker::PhantomData;
+
+use {Void, MapChars, map_chars, is_whitespace};
+
+/// The number of characters buffered at the start of a line while deciding if it is a header or the body
+const LOOKAHEAD: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub enum EncapsulatedHeaderError<Location, HeaderError> {
+    MissingExpected(char),
+    Mismatch {
+        location: Location,
+        expected: char,
+        found: char,
+    },
+    HeaderError{
+        location: Location,
+        error: HeaderError
+    },
+}
+
+impl<Loc: fmt::Display, HeaderError> fmt::Display for EncapsulatedHeaderError<Loc, HeaderError> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::EncapsulatedHeaderError::*;
+        match *self {
+            MissingExpected(expected) => write!(f, "expected {:?}, found the end of the input", expected),
+            Mismatch{ref location, expected, found} => write!(f, "expected {:?} at {}, found {:?}", expected, location, found),
+            HeaderError{ref location, ..} => write!(f, "invalid header at {}", location),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Loc, HeaderError> ::std::error::Error for EncapsulatedHeaderError<Loc, HeaderError>
+where Loc: fmt::Debug + fmt::Display,
+    HeaderError: ::std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn (::std::error::Error) + 'static)> {
+        match *self {
+            EncapsulatedHeaderError::HeaderError{ref error, ..} => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// A trait for receiving RFC 1421 encapsulated headers (e.g. `Proc-Type` and `DEK-Info`)
+pub trait Header {
+    /// The type of any errors which might occur while accumulating the headers
+    type HeaderError;
+
+    /// Signals the start of a new header.
+    ///
+    /// Defaults to `Ok(())`.
+    fn start(&mut self) -> Result<(), Self::HeaderError> {
+        Ok(())
+    }
+
+    /// Adds a character to the name of the current header.
+    fn push_name(&mut self, c: char) -> Result<(), Self::HeaderError>;
+
+    /// Adds a character to the value of the current header.
+    ///
+    /// Continuation lines are unfolded: the line break is dropped but the leading whitespace is kept.
+    fn push_value(&mut self, c: char) -> Result<(), Self::HeaderError>;
+
+    /// Signals that the current header is complete.
+    ///
+    /// Defaults to `Ok(())`.
+    fn complete(&mut self) -> Result<(), Self::HeaderError> {
+        Ok(())
+    }
+}
+
+/// A header "accumulator" which discards the headers
+pub struct DiscardHeaders;
+
+impl Header for DiscardHeaders {
+    type HeaderError = Void;
+    fn push_name(&mut self, _: char) -> Result<(), Self::HeaderError> {
+        Ok(())
+    }
+    fn push_value(&mut self, _: char) -> Result<(), Self::HeaderError> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Header for &mut Vec<(String, String)> {
+    type HeaderError = Void;
+    fn start(&mut self) -> Result<(), Self::HeaderError> {
+        self.push((String::new(), String::new()));
+        Ok(())
+    }
+    fn push_name(&mut self, c: char) -> Result<(), Self::HeaderError> {
+        if let Some(header) = self.last_mut() {
+            header.0.push(c);
+        }
+        Ok(())
+    }
+    fn push_value(&mut self, c: char) -> Result<(), Self::HeaderError> {
+        if let Some(header) = self.last_mut() {
+            header.1.push(c);
+        }
+        Ok(())
+    }
+}
+
+/// The characters read past the end of the headers, which belong to the body.
+///
+/// Chain this with the rest of the stream before parsing the body.
+pub struct Remainder<Loc, E> {
+    buffer: [Option<(Loc, char)>; LOOKAHEAD],
+    next: usize,
+    marker: PhantomData<fn() -> E>,
+}
+
+impl<Loc, E> Iterator for Remainder<Loc, E> {
+    type Item = Result<(Loc, char), E>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let c = self.buffer.get_mut(self.next).and_then(Option::take);
+        self.next += 1;
+        c.map(Ok)
+    }
+}
+
+impl<Loc> Remainder<Loc, Void> {
+    pub fn chain_chars<S>(self, stream: S) -> Chain<Self, MapChars<Loc, S>>
+    where S: Iterator<Item = (Loc, char)> {
+        self.chain(stream.map(map_chars as fn(_) -> _))
+    }
+}
+
+/// Parses the encapsulated headers between the `BEGIN` boundary and the body.
+///
+/// A block without headers is also accepted.
+/// Some of the body may be read while looking for headers, see `Remainder`.
+pub struct HeaderParser<Loc, H: Header, S> {
+    stream: S,
+    state: HeaderParserState<Loc, H>,
+    result: Result<(), EncapsulatedHeaderError<Loc, H::HeaderError>>,
+}
+
+pub(crate) struct HeaderParserState<Loc, H> {
+    header: H,
+    stage: Stage,
+    buffer: [Option<(Loc, char)>; LOOKAHEAD],
+    len: usize,
+}
+
+#[derive(Clone, Copy)]
+enum Stage {
+    /// Buffering the start of a line until it's clear whether it's a header or the body
+    LineStart,
+    Name,
+    ValueStart,
+    Value,
+    /// After the end of a value line, which may be followed by a continuation line
+    LineEnd{
+        cr: bool,
+    },
+    Done,
+}
+
+impl<Loc, H, E, S> HeaderParser<Loc, H, S>
+where H: Header,
+    S: Iterator<Item = Result<(Loc, char), E>>
+    {
+    pub fn new(stream: S, header: H) -> Self {
+        HeaderParser{
+            stream, state: HeaderParserState::new(header), result: Ok(()),
+        }
+    }
+
+    /// Call after `next` returns None
+    pub fn complete(self) -> Result<Remainder<Loc, E>, EncapsulatedHeaderError<Loc, H::HeaderError>> {
+        let HeaderParser{state, result, ..} = self;
+        result.map(|()| state.into_remainder())
+    }
+}
+
+impl<Loc, H, S> HeaderParser<Loc, H, Map<S, fn((Loc, char)) -> Result<(Loc, char), Void>>>
+where H: Header,
+    S: Iterator<Item = (Loc, char)>
+    {
+    pub fn from_chars(stream: S, header: H) -> Self {
+        Self::new(stream.map(map_chars), header)
+    }
+}
+
+impl<Loc, H, E, S> Iterator for HeaderParser<Loc, H, S>
+where H: Header,
+    S: Iterator<Item = Result<(Loc, char), E>>
+{
+    type Item = E;
+    fn next(&mut self) -> Option<E> {
+        if self.result.is_err() {
+            return None;
+        }
+        match self.state.process(&mut self.stream) {
+            Err(e) => {
+                self.result = Err(e);
+                None
+            },
+            Ok(e) => e,
+        }
+    }
+}
+
+impl<Loc, H: Header> HeaderParserState<Loc, H> {
+    pub(crate) fn new(header: H) -> Self {
+        HeaderParserState{
+            header, stage: Stage::LineStart, buffer: Default::default(), len: 0,
+        }
+    }
+
+    pub(crate) fn into_remainder<E>(self) -> Remainder<Loc, E> {
+        Remainder{buffer: self.buffer, next: 0, marker: PhantomData}
+    }
+
+    fn buffer(&mut self, c: (Loc, char)) {
+        self.buffer[self.len] = Some(c);
+        self.len += 1;
+    }
+
+    /// Starts a header, moving the buffered characters into its name
+    fn start(&mut self) -> Result<(), EncapsulatedHeaderError<Loc, H::HeaderError>> {
+        use self::EncapsulatedHeaderError::*;
+
+        let header = &mut self.header;
+        for (i, c) in self.buffer.iter_mut().take(self.len).enumerate() {
+            let (location, c) = c.take().unwrap();
+            let result = if i == 0 {
+                header.start()
+            } else {
+                Ok(())
+            };
+            if let Err(error) = result.and_then(|()| header.push_name(c)) {
+                return Err(HeaderError{location, error});
+            }
+        }
+        self.len = 0;
+        Ok(())
+    }
+
+    /// Returns `Ok(Some(e))` if the stream produced an error, `Ok(None)` once the headers are done.
+    pub(crate) fn process<'a, E: 'a>(&mut self, stream: &'a mut dyn Iterator<Item = Result<(Loc, char), E>>) -> Result<Option<E>, EncapsulatedHeaderError<Loc, H::HeaderError>> {
+        use self::EncapsulatedHeaderError::*;
+        use self::Stage::*;
+
+        loop {
+            if let Done = self.stage {
+                return Ok(None);
+            }
+
+            let (location, c) = match stream.next() {
+                Some(Err(e)) => return Ok(Some(e)),
+                None => return Err(MissingExpected(match self.stage {
+                    Name => ':',
+                    _ => '-',
+                })),
+                Some(Ok(c)) => c,
+            };
+
+            if let LineEnd{cr} = self.stage {
+                match c {
+                    '\n' if cr => {
+                        self.stage = LineEnd{cr: false};
+                        continue;
+                    },
+                    ' ' | '\t' => self.stage = Value,
+                    _ => {
+                        if let Err(error) = self.header.complete() {
+                            return Err(HeaderError{location, error});
+                        }
+                        self.stage = LineStart;
+                    },
+                }
+            }
+
+            match self.stage {
+                LineStart => match c {
+                    _ if self.len == 0 && is_whitespace(&(&location, c)) => {},
+                    ':' if self.len != 0 => {
+                        self.start()?;
+                        self.stage = ValueStart;
+                    },
+                    '-' if self.len != 0 => {
+                        self.buffer((location, c));
+                        self.start()?;
+                        self.stage = Name;
+                    },
+                    'A'..='Z' | 'a'..='z' | '0'..='9' if self.len < LOOKAHEAD - 1 => self.buffer((location, c)),
+                    _ => {
+                        // Not a header, so this is the start of the body (or the END boundary)
+                        self.buffer((location, c));
+                        self.stage = Done;
+                    },
+                },
+                Name => match c {
+                    ':' => self.stage = ValueStart,
+                    'A'..='Z' | 'a'..='z' | '0'..='9' | '-' => if let Err(error) = self.header.push_name(c) {
+                        return Err(HeaderError{location, error});
+                    },
+                    found => return Err(Mismatch{location, expected: ':', found}),
+                },
+                ValueStart | Value => match c {
+                    ' ' | '\t' if matches!(self.stage, ValueStart) => {},
+                    '\r' => self.stage = LineEnd{cr: true},
+                    '\n' => self.stage = LineEnd{cr: false},
+                    c => {
+                        self.stage = Value;
+                        if let Err(error) = self.header.push_value(c) {
+                            return Err(HeaderError{location, error});
+                        }
+                    },
+                },
+                LineEnd{..} | Done => unreachable!(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HeaderParser, DiscardHeaders, EncapsulatedHeaderError};
+
+    #[test]
+    fn test_no_headers() {
+        fn helper(input: &str, remainder: &str) {
+            let mut input = input.chars().enumerate();
+            let mut parser = HeaderParser::from_chars(&mut input, DiscardHeaders);
+            assert_eq!(parser.next(), None);
+            let r = parser.complete().unwrap();
+            assert!(r.map(|c| c.unwrap().1).eq(remainder.chars()));
+        }
+
+        helper("\nMIIBPQIBAAJBAOsfi5AG\n", "MIIBPQIBAAJBAOsf");
+        helper("\r\n  \nMIIB+QIB\n", "MIIB+");
+        helper("\nMIIB\nAAJB\n", "MIIB\n");
+        helper("\n-----END", "-");
+        helper("\nAA==\n", "AA=");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_headers() {
+        fn helper(input: &str, headers: &[(&str, &str)], remainder: &str) {
+            let mut list = Vec::new();
+            {
+                let mut input = input.chars().enumerate();
+                let mut parser = HeaderParser::from_chars(&mut input, &mut list);
+                assert_eq!(parser.next(), None);
+                let r = parser.complete().unwrap();
+                assert!(r.map(|c| c.unwrap().1).eq(remainder.chars()));
+            }
+            let expected: Vec<_> = headers.iter().map(|&(n, v)| (n.to_owned(), v.to_owned())).collect();
+            assert_eq!(list, expected);
+        }
+
+        helper(
+            "\nProc-Type: 4,ENCRYPTED\nDEK-Info: AES-128-CBC,2FE8B1A5\n\nMIIB\n",
+            &[("Proc-Type", "4,ENCRYPTED"), ("DEK-Info", "AES-128-CBC,2FE8B1A5")],
+            "MIIB\n",
+        );
+        helper(
+            "\r\nProc-Type: 4,MIC-ONLY\r\nContent-Domain: RFC822\r\n\r\n-----END",
+            &[("Proc-Type", "4,MIC-ONLY"), ("Content-Domain", "RFC822")],
+            "-",
+        );
+        helper(
+            "\nComment:first\n  second\n\tthird\nAAAA\n",
+            &[("Comment", "first  second\tthird")],
+            "AAAA\n",
+        );
+    }
+
+    #[test]
+    fn test_header_errors() {
+        let mut parser = HeaderParser::from_chars("\nProc-Type 4".chars().enumerate(), DiscardHeaders);
+        assert_eq!(parser.next(), None);
+        assert_eq!(parser.complete().err(), Some(EncapsulatedHeaderError::Mismatch{location: 10, expected: ':', found: ' '}));
+
+        let mut parser = HeaderParser::from_chars("\nProc-Type: 4".chars().enumerate(), DiscardHeaders);
+        assert_eq!(parser.next(), None);
+        assert_eq!(parser.complete().err(), Some(EncapsulatedHeaderError::MissingExpected('-')));
+    }
+}
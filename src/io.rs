@@ -0,0 +1,424 @@
+use std::io::{self, BufRead, Read, Write};
+use std::mem::replace;
+use std::vec;
+
+use boundary::{BoundaryType, BoundaryParser, BoundaryParserState};
+use body::{BodyConfig, Sextets, SingleState};
+use encoder::{EncoderConfig, encode_quantum};
+use error::{PemError, MatchBegin};
+use {NonAsciiError, map_bytes};
+
+/// The error carried by the `io::Error`s produced while parsing.
+///
+/// Recover it with `io::Error::get_ref` and `downcast_ref`.
+pub type ReadError = PemError<usize, NonAsciiError<usize>>;
+
+impl From<ReadError> for io::Error {
+    fn from(e: ReadError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// The characters of a `BufRead`, located by byte offset
+struct ByteStream<R> {
+    inner: R,
+    offset: usize,
+}
+
+impl<R: BufRead> Iterator for ByteStream<R> {
+    type Item = Result<(usize, char), io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let found = loop {
+            match self.inner.fill_buf() {
+                Ok(buf) => match buf.first() {
+                    Some(&found) => break found,
+                    None => return None,
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Some(Err(e)),
+            }
+        };
+        self.inner.consume(1);
+
+        let location = self.offset;
+        self.offset += 1;
+        Some(map_bytes((location, found)).map_err(|e| PemError::SourceError(e).into()))
+    }
+}
+
+/// Reads the decoded body of a single PEM block from a `BufRead`.
+///
+/// The `BEGIN` boundary is parsed by the constructor, and the `END` boundary is checked before reporting the end of the data.
+/// Wrap plain `Read`ers in a `BufReader`.
+pub struct Reader<R> {
+    stream: ByteStream<R>,
+    label: String,
+    sextets: Sextets,
+    state: ReaderState,
+    /// An error encountered after some bytes were already read
+    error: Option<io::Error>,
+}
+
+enum ReaderState {
+    Body(SingleState),
+    End(BoundaryParserState<usize, MatchBegin<vec::IntoIter<char>>>),
+    Done,
+    /// A parsing error was returned
+    Failed,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(inner: R) -> io::Result<Self> {
+        Self::with_config(inner, BodyConfig::default())
+    }
+
+    pub fn with_config(inner: R, config: BodyConfig) -> io::Result<Self> {
+        let mut stream = ByteStream{inner, offset: 0};
+        let mut label = String::new();
+        {
+            let mut parser = BoundaryParser::new(BoundaryType::Begin, &mut stream, &mut label);
+            if let Some(e) = parser.next() {
+                return Err(e);
+            }
+            parser.complete().map_err(PemError::begin)?;
+        }
+
+        Ok(Reader{
+            stream,
+            label,
+            sextets: Sextets::new(config),
+            state: ReaderState::Body(SingleState::ZeroBits),
+            error: None,
+        })
+    }
+
+    /// The label of the `BEGIN` boundary
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn into_inner(self) -> R {
+        self.stream.inner
+    }
+
+    fn next(&mut self) -> io::Result<Option<u8>> {
+        loop {
+            match replace(&mut self.state, ReaderState::Done) {
+                ReaderState::Body(state) => match state.process(&mut self.stream, &mut self.sextets) {
+                    (Some(state), Some(Err(e))) => return Err(match e.without_source() {
+                        Ok(e) => self.fail(ReadError::Body(e)),
+                        Err(e) => {
+                            self.state = ReaderState::Body(state);
+                            e
+                        },
+                    }),
+                    (Some(state), result) => {
+                        self.state = ReaderState::Body(state);
+                        if let Some(Ok(b)) = result {
+                            return Ok(Some(b));
+                        }
+                    },
+                    (None, _) => {
+                        let label: Vec<_> = self.label.chars().collect();
+                        self.state = ReaderState::End(BoundaryParserState::new(BoundaryType::End, MatchBegin(label.into_iter())));
+                    },
+                },
+                ReaderState::End(state) => return match state.process(&mut self.stream, &mut 0) {
+                    Ok(None) => Ok(None),
+                    Ok(Some((state, e))) => {
+                        self.state = ReaderState::End(state);
+                        Err(e)
+                    },
+                    Err(e) => Err(self.fail(ReadError::end_mismatch(e))),
+                },
+                ReaderState::Done => return Ok(None),
+                ReaderState::Failed => return Err(io::Error::new(io::ErrorKind::InvalidData, "the PEM block is invalid")),
+            }
+        }
+    }
+
+    /// Stops parsing, so that reading again doesn't look like the end of the data
+    fn fail(&mut self, e: ReadError) -> io::Error {
+        self.state = ReaderState::Failed;
+        e.into()
+    }
+}
+
+impl<R: BufRead> Read for Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+
+        let mut len = 0;
+        for b in buf.iter_mut() {
+            match self.next() {
+                Ok(Some(v)) => *b = v,
+                Ok(None) => break,
+                Err(e) => if len == 0 {
+                    return Err(e);
+                } else {
+                    self.error = Some(e);
+                    break;
+                },
+            }
+            len += 1;
+        }
+        Ok(len)
+    }
+}
+
+/// Encodes the bytes written to it as a PEM block, writing each line to the inner `Write` as it fills.
+///
+/// The `BEGIN` boundary is written by the constructor.
+/// Once `write` returns `Ok` the bytes are encoded; if the inner `Write` then fails, the encoded output is kept and written first by the next call.
+/// Call `finish` to write the `END` boundary; dropping the `Writer` does so too, but ignores any errors.
+pub struct Writer<W: Write> {
+    inner: Option<W>,
+    label: String,
+    config: EncoderConfig,
+    quantum: [u8; 3],
+    len: usize,
+    column: usize,
+    /// Encoded output not yet accepted by the inner `Write`
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(inner: W, label: &str) -> io::Result<Self> {
+        Self::with_config(inner, label, EncoderConfig::default())
+    }
+
+    pub fn with_config(mut inner: W, label: &str, config: EncoderConfig) -> io::Result<Self> {
+        write!(inner, "-----BEGIN {}-----{}", label, config.line_ending.as_str())?;
+        Ok(Writer{
+            inner: Some(inner),
+            label: label.to_owned(),
+            config,
+            quantum: [0; 3],
+            len: 0,
+            column: 0,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Writes the final quantum and the `END` boundary, returning the inner `Write`
+    pub fn finish(mut self) -> io::Result<W> {
+        let result = self.end();
+        let inner = self.inner.take().unwrap();
+        result.map(|()| inner)
+    }
+
+    fn encode(&mut self) {
+        for c in encode_quantum(&self.quantum[..self.len]).iter() {
+            if self.column == self.config.line_length && self.column != 0 {
+                self.column = 0;
+                self.pending.extend_from_slice(self.config.line_ending.as_str().as_bytes());
+            }
+            self.pending.push(*c as u8);
+            self.column += 1;
+        }
+        self.len = 0;
+    }
+
+    /// Writes as much of `pending` as the inner `Write` accepts
+    fn write_pending(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        while !self.pending.is_empty() {
+            match inner.write(&self.pending) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero, "failed to write the encoded output")),
+                Ok(len) => {
+                    self.pending.drain(..len);
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn end(&mut self) -> io::Result<()> {
+        if self.len != 0 {
+            self.encode();
+        }
+        let line_ending = self.config.line_ending.as_str();
+        if self.column != 0 {
+            self.pending.extend_from_slice(line_ending.as_bytes());
+        }
+        self.pending.extend_from_slice(b"-----END ");
+        self.pending.extend_from_slice(self.label.as_bytes());
+        self.pending.extend_from_slice(b"-----");
+        if self.config.trailing_newline {
+            self.pending.extend_from_slice(line_ending.as_bytes());
+        }
+
+        self.write_pending()?;
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Nothing from `buf` is encoded until the output of earlier calls is written
+        self.write_pending()?;
+        for &b in buf {
+            self.quantum[self.len] = b;
+            self.len += 1;
+            if self.len == 3 {
+                self.encode();
+            }
+        }
+        // `buf` is already encoded, so an error is left for the next call
+        let _ = self.write_pending();
+        Ok(buf.len())
+    }
+
+    /// Flushes the inner `Write`. Up to 2 bytes may still be held back until `finish`.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for Writer<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.end();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::io::{self, Read, Write};
+    use std::rc::Rc;
+
+    use body::BodyError;
+    use boundary::EncapsulationBoundaryError;
+    use encoder::{Encoder, EncoderConfig, LineEnding};
+    use super::{Reader, ReadError, Writer};
+
+    fn read(input: &str) -> Result<Vec<u8>, io::Error> {
+        let mut reader = Reader::new(input.as_bytes())?;
+        assert_eq!(reader.label(), "DATA");
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn read_error(input: &str) -> ReadError {
+        let e = read(input).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        *e.into_inner().unwrap().downcast::<ReadError>().unwrap()
+    }
+
+    #[test]
+    fn test_read() {
+        assert_eq!(read("-----BEGIN DATA-----\n-----END DATA-----\n").unwrap(), b"");
+        assert_eq!(read("-----BEGIN DATA-----\nZm9vYmFy\n-----END DATA-----\n").unwrap(), b"foobar");
+        assert_eq!(read("\n-----BEGIN DATA-----\r\nZm9v\r\nYmE=\r\n-----END DATA-----").unwrap(), b"fooba");
+
+        assert_eq!(read_error("-----BEGIN DATA-----\nZm9v\n-----END DAT-----"),
+            ReadError::LabelMismatch{location: 39});
+        assert_eq!(read_error("-----BEGIN DATA-----\nZm9v\n-----END DATA----"),
+            ReadError::EndBoundary(EncapsulationBoundaryError::MissingExpected('-')));
+        assert_eq!(read_error("-----BEGIN DATA-----\nZm9v!mFy\n-----END DATA-----"),
+            ReadError::Body(BodyError::InvalidCharacter{location: 25, found: '!'}));
+        assert_eq!(read_error("-----BEGIN DATA-----\nZm9v\u{e9}mFy\n-----END DATA-----"),
+            ReadError::SourceError(::NonAsciiError{location: 25, found: 0xc3}));
+
+        // Reading again after a parsing error fails again, rather than reporting the end of the data
+        for input in &["-----BEGIN DATA-----\nZm9v\n-----END DAT-----", "-----BEGIN DATA-----\nZm9v!mFy\n-----END DATA-----"] {
+            let mut reader = Reader::new(input.as_bytes()).unwrap();
+            let mut data = Vec::new();
+            assert!(reader.read_to_end(&mut data).is_err());
+            assert_eq!(reader.read(&mut [0; 8]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_write() {
+        fn helper(input: &[u8], config: EncoderConfig) {
+            let expected: String = Encoder::with_config(input.iter().cloned(), "DATA".chars(), config).collect();
+            for chunk in 1..5 {
+                let mut writer = Writer::with_config(Vec::new(), "DATA", config).unwrap();
+                for bytes in input.chunks(chunk) {
+                    writer.write_all(bytes).unwrap();
+                }
+                let output = writer.finish().unwrap();
+                assert_eq!(String::from_utf8(output).unwrap(), expected);
+            }
+        }
+
+        let data: Vec<u8> = (0..200).collect();
+        let crlf = EncoderConfig{line_ending: LineEnding::CrLf, trailing_newline: false, ..EncoderConfig::default()};
+        let unwrapped = EncoderConfig{line_length: 0, ..EncoderConfig::default()};
+        for &len in &[0, 1, 2, 3, 47, 48, 49, 200] {
+            helper(&data[..len], EncoderConfig::default());
+            helper(&data[..len], crlf);
+            helper(&data[..len], unwrapped);
+        }
+
+        // Dropping also writes the END boundary
+        let mut output = Vec::new();
+        {
+            let mut writer = Writer::new(&mut output, "DATA").unwrap();
+            writer.write_all(b"foobar").unwrap();
+        }
+        assert_eq!(output, b"-----BEGIN DATA-----\nZm9vYmFy\n-----END DATA-----\n");
+
+        let mut reader = Reader::new(&output[..]).unwrap();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"foobar");
+    }
+
+    /// Accepts up to 5 bytes at a time, failing every other call while `failing` is set
+    struct Flaky {
+        output: Vec<u8>,
+        fail: bool,
+        failing: Rc<Cell<bool>>,
+    }
+
+    impl Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.fail = !self.fail;
+            if self.fail && self.failing.get() {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            let len = buf.len().min(5);
+            self.output.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_errors() {
+        let data: Vec<u8> = (0..200).collect();
+        let expected: String = Encoder::new(data.iter().cloned(), "DATA".chars()).collect();
+
+        let failing = Rc::new(Cell::new(false));
+        let mut writer = Writer::new(Flaky{output: Vec::new(), fail: false, failing: failing.clone()}, "DATA").unwrap();
+        failing.set(true);
+        // Retry each write until it's accepted, as `io::Write` allows
+        for bytes in data.chunks(7) {
+            loop {
+                match writer.write(bytes) {
+                    Ok(len) => break assert_eq!(len, bytes.len()),
+                    Err(e) => assert_eq!(e.kind(), io::ErrorKind::WouldBlock),
+                }
+            }
+        }
+        while writer.flush().is_err() {}
+        failing.set(false);
+
+        let output = writer.finish().unwrap().output;
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+}
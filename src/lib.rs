@@ -1,12 +1,27 @@
-#![cfg_attr(feature = "generators", feature(generators, generator_trait, conservative_impl_trait))]
-
 #![cfg_attr(not(feature = "std"), no_std)]
 
 #[cfg(feature = "std")]
 extern crate core;
+#[cfg(feature = "futures")]
+extern crate futures_core;
+#[cfg(feature = "futures")]
+extern crate futures_io;
 
 pub mod body;
 pub mod boundary;
+pub mod document;
+pub mod encoder;
+pub mod error;
+#[cfg(feature = "futures")]
+pub mod futures;
+pub mod header;
+#[cfg(feature = "std")]
+pub mod io;
+pub mod label;
+pub mod location;
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod push;
 
 #[cfg(feature = "generators")]
 pub mod generator;
@@ -15,9 +30,48 @@ pub mod generator;
 #[derive(Clone, Copy, Debug, PartialEq, Eq)] 
 pub enum Void {}
 
+impl core::fmt::Display for Void {
+    fn fmt(&self, _: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Void {}
+
+/// The stream produced from `(Loc, char)` pairs by the `from_chars` constructors
+pub type MapChars<Loc, S> = core::iter::Map<S, fn((Loc, char)) -> Result<(Loc, char), Void>>;
+
 fn map_chars<Loc>(c: (Loc, char)) -> Result<(Loc, char), Void> {
     Ok(c)
 }
+
+/// A byte outside of the ASCII range, which can't appear in PEM
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NonAsciiError<Loc> {
+    pub location: Loc,
+    pub found: u8,
+}
+
+impl<Loc: core::fmt::Display> core::fmt::Display for NonAsciiError<Loc> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "non-ASCII byte 0x{:02x} at {}", self.found, self.location)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Loc: core::fmt::Debug + core::fmt::Display> std::error::Error for NonAsciiError<Loc> {}
+
+/// The stream produced from `(Loc, u8)` pairs by the `from_bytes` constructors
+pub type MapBytes<Loc, S> = core::iter::Map<S, fn((Loc, u8)) -> Result<(Loc, char), NonAsciiError<Loc>>>;
+
+fn map_bytes<Loc>((location, found): (Loc, u8)) -> Result<(Loc, char), NonAsciiError<Loc>> {
+    if found.is_ascii() {
+        Ok((location, found as char))
+    } else {
+        Err(NonAsciiError{location, found})
+    }
+}
  
 #[cfg(feature = "std")] 
 fn is_whitespace<Loc>(&(_, ref c): &(Loc, char)) -> bool { 
@@ -0,0 +1,104 @@
+use core::fmt;
+
+/// The position of a character in the input
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Location {
+    /// The line number, starting at 1
+    pub line: usize,
+    /// The column number in characters, starting at 1
+    pub column: usize,
+    /// The number of bytes (as UTF-8) before the character
+    pub byte_offset: usize,
+    /// The number of characters before the character
+    pub char_offset: usize,
+}
+
+impl Default for Location {
+    /// The location of the first character
+    fn default() -> Self {
+        Location{
+            line: 1,
+            column: 1,
+            byte_offset: 0,
+            char_offset: 0,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// Pairs each character with its `Location`, for use as the `Loc` of the parsers.
+///
+/// `"\n"`, `"\r\n"`, and a lone `'\r'` each end a line.
+pub struct Locations<I> {
+    chars: I,
+    next: Location,
+    cr: bool,
+}
+
+impl<I: Iterator<Item = char>> Locations<I> {
+    pub fn new(chars: I) -> Self {
+        Locations{
+            chars, next: Location::default(), cr: false,
+        }
+    }
+}
+
+impl<I: Iterator<Item = char>> Iterator for Locations<I> {
+    type Item = (Location, char);
+
+    fn next(&mut self) -> Option<(Location, char)> {
+        let c = self.chars.next()?;
+
+        if self.cr && c != '\n' {
+            // The previous '\r' was on its own, so it ended the line
+            self.next.line += 1;
+            self.next.column = 1;
+        }
+        let location = self.next;
+
+        self.cr = c == '\r';
+        self.next.byte_offset += c.len_utf8();
+        self.next.char_offset += 1;
+        if c == '\n' {
+            self.next.line += 1;
+            self.next.column = 1;
+        } else {
+            self.next.column += 1;
+        }
+
+        Some((location, c))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chars.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Location, Locations};
+
+    #[test]
+    fn test_locations() {
+        fn helper(input: &str, expected: &[(usize, usize)]) {
+            let mut byte_offset = 0;
+            let mut locations = Locations::new(input.chars());
+            for (char_offset, (&(line, column), c)) in expected.iter().zip(input.chars()).enumerate() {
+                assert_eq!(locations.next(), Some((Location{line, column, byte_offset, char_offset}, c)));
+                byte_offset += c.len_utf8();
+            }
+            assert_eq!(locations.next(), None);
+        }
+
+        helper("ab\ncd", &[(1, 1), (1, 2), (1, 3), (2, 1), (2, 2)]);
+        helper("a\r\nb", &[(1, 1), (1, 2), (1, 3), (2, 1)]);
+        helper("a\rb\r", &[(1, 1), (1, 2), (2, 1), (2, 2)]);
+        helper("\r\r\n\n", &[(1, 1), (2, 1), (2, 2), (3, 1)]);
+        helper("\u{211D}\u{211D}\nx", &[(1, 1), (1, 2), (1, 3), (2, 1)]);
+    }
+}
@@ -0,0 +1,42 @@
+use body::{BodyConfig, Lines, Padding, Whitespace};
+
+/// Which of the RFC 7468 section 3 grammars to accept.
+///
+/// Pass it to `BoundaryParser::with_profile` for the boundaries, and convert it `into` a `BodyConfig` for `Chunked` and `Single`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// `stricttextualmsg`: nothing but a line ending after each boundary, a non-empty body in lines of 64 characters without other whitespace or empty lines, exact padding, and a canonical encoding
+    Strict,
+    /// `textualmsg`: spaces and tabs may end the boundary lines and the lines of the body, and padding may be left out.
+    ///
+    /// Unlike the grammar, empty lines are accepted anywhere in the body, not just at its start and end.
+    Standard,
+    /// `laxtextualmsg`: whitespace may surround the boundaries, ASCII whitespace may appear in the body, and padding may be left out
+    Lax,
+}
+
+impl From<Profile> for BodyConfig {
+    fn from(profile: Profile) -> Self {
+        match profile {
+            Profile::Strict => BodyConfig{
+                padding: Padding::Strict,
+                canonical: true,
+                max_line_length: 64,
+                exact_lines: true,
+                whitespace: Whitespace::LineBreaks,
+                lines: Lines::Strict,
+            },
+            Profile::Standard => BodyConfig{
+                padding: Padding::Optional,
+                whitespace: Whitespace::Trailing,
+                lines: Lines::EndOnNewLine,
+                ..BodyConfig::default()
+            },
+            Profile::Lax => BodyConfig{
+                padding: Padding::Optional,
+                whitespace: Whitespace::Ascii,
+                ..BodyConfig::default()
+            },
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use std::mem::replace;
+use std::slice;
+use std::vec;
+
+use boundary::{BoundaryType, BoundaryParserState, EncapsulationBoundaryError, Label, Progress};
+use body::{BodyConfig, Sextets, SingleState};
+use error::{PemError, MatchBegin};
+use {NonAsciiError, Void, map_bytes};
+
+/// The errors reported by `PushParser`
+pub type PushError = PemError<usize, NonAsciiError<usize>>;
+
+/// Something found by `PushParser`
+#[derive(Debug, PartialEq)]
+pub enum Event<'a> {
+    /// A `BEGIN` boundary, with its label
+    BeginLabel(&'a str),
+    /// Some of the decoded body. A body may be split across any number of events.
+    BodyBytes(&'a [u8]),
+    /// An `END` boundary matching the `BEGIN` label
+    End,
+    /// Parsing failed, and the rest of the input will be ignored
+    Error(PushError),
+}
+
+/// Receives the `Event`s of a `PushParser`
+pub trait Sink {
+    fn event(&mut self, event: Event);
+}
+
+impl<F: FnMut(Event)> Sink for F {
+    fn event(&mut self, event: Event) {
+        self(event)
+    }
+}
+
+/// Parses PEM blocks from chunks of bytes as they arrive, without blocking on a source.
+///
+/// Each call to `feed` parses as far as it can, keeping any partial boundary or quantum for the next call.
+/// Call `finish` at the end of the input. Locations are byte offsets from the start of the input.
+/// Explanatory text before and between blocks is skipped.
+pub struct PushParser {
+    offset: usize,
+    config: BodyConfig,
+    sextets: Sextets,
+    state: PushState,
+    label: String,
+    /// Decoded bytes not yet passed to the sink
+    bytes: Vec<u8>,
+}
+
+enum PushState {
+    Begin(BoundaryParserState<usize, Collect>),
+    Body(SingleState),
+    End(BoundaryParserState<usize, MatchBegin<vec::IntoIter<char>>>),
+    /// An error was reported
+    Failed,
+}
+
+/// Collects the `BEGIN` label, which is handed back when the boundary is complete
+struct Collect(String);
+
+impl Label for Collect {
+    type LabelError = Void;
+
+    fn push(&mut self, found: char) -> Result<Option<char>, Void> {
+        self.0.push(found);
+        Ok(None)
+    }
+}
+
+/// Why the stream stopped short
+enum Pause {
+    /// The bytes fed so far are used up
+    Empty,
+    NonAscii(NonAsciiError<usize>),
+}
+
+/// The characters of a single chunk
+struct Chunk<'a> {
+    bytes: slice::Iter<'a, u8>,
+    offset: usize,
+    /// Whether the chunk ends the input
+    last: bool,
+}
+
+impl<'a> Iterator for Chunk<'a> {
+    type Item = Result<(usize, char), Pause>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let found = match self.bytes.next() {
+            Some(&found) => found,
+            None if self.last => return None,
+            None => return Some(Err(Pause::Empty)),
+        };
+
+        let location = self.offset;
+        self.offset += 1;
+        Some(map_bytes((location, found)).map_err(Pause::NonAscii))
+    }
+}
+
+impl PushParser {
+    pub fn new() -> Self {
+        Self::with_config(BodyConfig::default())
+    }
+
+    pub fn with_config(config: BodyConfig) -> Self {
+        PushParser{
+            offset: 0,
+            config,
+            sextets: Sextets::new(config),
+            state: PushState::Begin(BoundaryParserState::new(BoundaryType::SkipToBegin, Collect(String::new()))),
+            label: String::new(),
+            bytes: Vec::new(),
+        }
+    }
+
+    /// Parses the next chunk of the input
+    pub fn feed<K: Sink>(&mut self, bytes: &[u8], sink: &mut K) {
+        self.run(bytes, false, sink);
+    }
+
+    /// Signals the end of the input, reporting an error if it ends inside a block
+    pub fn finish<K: Sink>(mut self, sink: &mut K) {
+        self.run(&[], true, sink);
+    }
+
+    fn run<K: Sink>(&mut self, bytes: &[u8], last: bool, sink: &mut K) {
+        let mut stream = Chunk{bytes: bytes.iter(), offset: self.offset, last};
+        self.process(&mut stream, sink);
+        self.flush(sink);
+        self.offset = stream.offset;
+    }
+
+    fn process<K: Sink>(&mut self, stream: &mut Chunk, sink: &mut K) {
+        loop {
+            match replace(&mut self.state, PushState::Failed) {
+                PushState::Begin(state) => match state.process_label(stream, &mut 0) {
+                    Ok(Progress::Yielded(state, pause)) => {
+                        self.state = PushState::Begin(state);
+                        return self.pause(pause, sink);
+                    },
+                    Ok(Progress::Complete(Collect(label))) => {
+                        sink.event(Event::BeginLabel(&label));
+                        self.label = label;
+                        self.sextets = Sextets::new(self.config);
+                        self.state = PushState::Body(SingleState::ZeroBits);
+                    },
+                    // Nothing but explanatory text after the last block
+                    Err(EncapsulationBoundaryError::MissingBoundary) => return,
+                    Err(e) => return self.fail(PemError::begin(e), sink),
+                },
+                PushState::Body(state) => match state.process(stream, &mut self.sextets) {
+                    (Some(state), Some(Ok(b))) => {
+                        self.bytes.push(b);
+                        self.state = PushState::Body(state);
+                    },
+                    (Some(state), Some(Err(e))) => match e.without_source() {
+                        Ok(e) => return self.fail(PemError::Body(e), sink),
+                        Err(pause) => {
+                            self.state = PushState::Body(state);
+                            return self.pause(pause, sink);
+                        },
+                    },
+                    (_, _) => {
+                        let label: Vec<_> = self.label.chars().collect();
+                        self.state = PushState::End(BoundaryParserState::new(BoundaryType::End, MatchBegin(label.into_iter())));
+                    },
+                },
+                PushState::End(state) => match state.process(stream, &mut 0) {
+                    Ok(Some((state, pause))) => {
+                        self.state = PushState::End(state);
+                        return self.pause(pause, sink);
+                    },
+                    Ok(None) => {
+                        self.flush(sink);
+                        sink.event(Event::End);
+                        self.state = PushState::Begin(BoundaryParserState::new(BoundaryType::SkipToBegin, Collect(String::new())));
+                    },
+                    Err(e) => return self.fail(PushError::end_mismatch(e), sink),
+                },
+                PushState::Failed => return,
+            }
+        }
+    }
+
+    fn pause<K: Sink>(&mut self, pause: Pause, sink: &mut K) {
+        if let Pause::NonAscii(e) = pause {
+            self.fail(PemError::SourceError(e), sink);
+        }
+    }
+
+    fn fail<K: Sink>(&mut self, e: PushError, sink: &mut K) {
+        self.flush(sink);
+        sink.event(Event::Error(e));
+        self.state = PushState::Failed;
+    }
+
+    fn flush<K: Sink>(&mut self, sink: &mut K) {
+        if !self.bytes.is_empty() {
+            sink.event(Event::BodyBytes(&self.bytes));
+            self.bytes.clear();
+        }
+    }
+}
+
+impl Default for PushParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use body::BodyError;
+    use boundary::EncapsulationBoundaryError;
+    use super::{Event, PushParser, PushError};
+    use NonAsciiError;
+
+    /// An `Event` which owns its data, with body bytes merged
+    #[derive(Debug, PartialEq)]
+    enum Owned {
+        Begin(String),
+        Bytes(Vec<u8>),
+        End,
+        Error(PushError),
+    }
+
+    fn parse(chunks: &[&[u8]]) -> Vec<Owned> {
+        let mut events = Vec::new();
+        {
+            let mut sink = |event: Event| {
+                let event = match event {
+                    Event::BeginLabel(label) => Owned::Begin(label.to_owned()),
+                    Event::BodyBytes(bytes) => {
+                        assert!(!bytes.is_empty());
+                        if let Some(&mut Owned::Bytes(ref mut v)) = events.last_mut() {
+                            v.extend_from_slice(bytes);
+                            return;
+                        }
+                        Owned::Bytes(bytes.to_vec())
+                    },
+                    Event::End => Owned::End,
+                    Event::Error(e) => Owned::Error(e),
+                };
+                events.push(event);
+            };
+
+            let mut parser = PushParser::new();
+            for chunk in chunks {
+                parser.feed(chunk, &mut sink);
+            }
+            parser.finish(&mut sink);
+        }
+        events
+    }
+
+    /// Checks that every way of splitting the input gives the same events
+    fn helper(input: &str, expected: &[Owned]) {
+        let input = input.as_bytes();
+        assert_eq!(parse(&[input]), expected);
+        for i in 0..=input.len() {
+            assert_eq!(parse(&[&input[..i], &input[i..]]), expected, "split at {}", i);
+        }
+        let bytes: Vec<&[u8]> = input.chunks(1).collect();
+        assert_eq!(parse(&bytes), expected);
+    }
+
+    #[test]
+    fn test_push() {
+        use self::Owned::*;
+
+        helper("", &[]);
+        helper("-----BEGIN DATA-----\n-----END DATA-----\n", &[Begin("DATA".to_owned()), End]);
+        helper("-----BEGIN DATA-----\nZm9vYmFy\n-----END DATA-----\n",
+            &[Begin("DATA".to_owned()), Bytes(b"foobar".to_vec()), End]);
+        helper("Text\r\n-----BEGIN A B-----\r\nZm9v\r\nYmE=\r\n-----END A B-----\r\nMore text\r\n-----BEGIN C-----\r\nYg==\r\n-----END C-----",
+            &[Begin("A B".to_owned()), Bytes(b"fooba".to_vec()), End, Begin("C".to_owned()), Bytes(b"b".to_vec()), End]);
+
+        helper("-----BEGIN DATA-----\nZm9v\n-----END DAT-----\n",
+            &[Begin("DATA".to_owned()), Bytes(b"foo".to_vec()), Error(PushError::LabelMismatch{location: 39})]);
+        helper("-----BEGIN DATA-----\nZm9v!mFy\n-----END DATA-----\n",
+            &[Begin("DATA".to_owned()), Bytes(b"foo".to_vec()), Error(PushError::Body(BodyError::InvalidCharacter{location: 25, found: '!'}))]);
+        helper("-----BEGIN DATA-----\nZm9v\u{e9}mFy\n-----END DATA-----\n",
+            &[Begin("DATA".to_owned()), Bytes(b"foo".to_vec()), Error(PushError::SourceError(NonAsciiError{location: 25, found: 0xc3}))]);
+        helper("-----BEGIN DATA-----\nZm9vYmFy\n",
+            &[Begin("DATA".to_owned()), Bytes(b"foobar".to_vec()), Error(PushError::Body(BodyError::MissingExpected('-')))]);
+        helper("-----BEGIN DATA-----\nZm9vYmFy\n-----END DATA--",
+            &[Begin("DATA".to_owned()), Bytes(b"foobar".to_vec()), Error(PushError::EndBoundary(EncapsulationBoundaryError::MissingExpected('-')))]);
+        helper("-----BEGIN DATA",
+            &[Error(PushError::BeginBoundary(EncapsulationBoundaryError::MissingExpected('-')))]);
+    }
+}
@@ -1,21 +1,21 @@
-#![cfg_attr(feature = "generators", feature(generators, generator_trait))]
-
 extern crate pem_iterator;
 extern crate pem;
 extern crate rand;
 
 use std::iter::repeat;
 
-#[cfg(feature = "generators")]
-use std::ops::{Generator, GeneratorState};
-
-use pem_iterator::boundary::{BoundaryType, BoundaryParser, LabelMatcher};
-use pem_iterator::body::{Chunked, Single};
-#[cfg(not(feature = "std"))]
-use pem_iterator::body::BytesContainer;
+use pem_iterator::boundary::{BoundaryType, BoundaryParser, LabelMatcher, DiscardLabel, EncapsulationBoundaryError};
+use pem_iterator::{Void, NonAsciiError};
+use pem_iterator::body::{Chunked, Single, BytesContainer, BodyConfig, BodyError, Lines, Padding, Whitespace};
+use pem_iterator::document::{Documents, DocumentsConfig};
+use pem_iterator::error::PemError;
+use pem_iterator::encoder::{Encoder, EncoderConfig, LineEnding};
+use pem_iterator::header::{HeaderParser, DiscardHeaders};
+use pem_iterator::location::{Location, Locations};
+use pem_iterator::profile::Profile;
 #[cfg(feature = "generators")]
 use pem_iterator::generator::{parse_boundary_chars, parse_body_chunked_chars,
-                              parse_body_single_chars};
+                              parse_body_single_chars, Generator, GeneratorState};
 use rand::{Rng, weak_rng};
 
 
@@ -231,6 +231,40 @@ fn pem(s: &str) -> Vec<u8> {
     pem::parse(&s).unwrap().contents
 }
 
+fn roundtrip(data: &[u8]) -> Vec<u8> {
+    let encoded: String = Encoder::new(data.iter().cloned(), "DATA".chars()).collect();
+    chunked(encoded.as_str())
+}
+
+fn roundtrip_config(data: &[u8]) {
+    for &line_length in &[64, 76, 4, 1, 0] {
+        for &line_ending in &[LineEnding::Lf, LineEnding::CrLf] {
+            for &trailing_newline in &[true, false] {
+                let config = EncoderConfig{line_length, line_ending, trailing_newline};
+                let encoded: String = Encoder::with_config(data.iter().cloned(), "DATA".chars(), config).collect();
+                assert_eq!(single(encoded.as_str()), data);
+            }
+        }
+    }
+
+    let config = EncoderConfig{line_ending: LineEnding::CrLf, ..EncoderConfig::default()};
+    let encoded: String = Encoder::with_config(data.iter().cloned(), "DATA".chars(), config).collect();
+    assert_eq!(encoded, pem::encode(&pem::Pem{tag: "DATA".to_owned(), contents: data.to_vec()}));
+}
+
+/// Decodes the body with `Chunked`, and encodes it again with `Encoder::from_chunks`
+fn reencode(s: &str) -> String {
+    let mut input = s.chars().enumerate();
+    let mut label = String::new();
+    {
+        let mut parser = BoundaryParser::from_chars(BoundaryType::Begin, &mut input, &mut label);
+        assert_eq!(parser.next(), None);
+        assert_eq!(parser.complete(), Ok(()));
+    }
+
+    let chunks: Result<Vec<_>, _> = Chunked::from_chars(&mut input).collect();
+    Encoder::from_chunks(chunks.unwrap().into_iter(), label.chars()).collect()
+}
 
 fn test(count: usize) {
     let s = gen(count);
@@ -247,6 +281,498 @@ fn test(count: usize) {
     #[cfg(feature = "generators")]
     assert_eq!(single, chunked_gen);
     assert_eq!(single, pem);
+    assert_eq!(single, roundtrip(single.as_slice()));
+    roundtrip_config(single.as_slice());
+    let encoded: String = Encoder::new(single.iter().cloned(), "DATA".chars()).collect();
+    assert_eq!(reencode(encoded.as_str()), encoded);
+}
+
+fn documents(s: &str) -> Vec<(String, Vec<u8>)> {
+    Documents::<_, String, BytesContainer<Vec<u8>>>::from_chars(s.chars().enumerate())
+        .map(|d| d.map(|d| (d.label, d.body.into())))
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn test_documents() {
+    let blocks = [gen(100), gen(1000), gen(4), gen(10000)];
+    let bundle = format!("{}\n{}\r\n\n{}\n{}\n\n", blocks[0], blocks[1], blocks[2], blocks[3]);
+
+    let expected: Vec<_> = pem::parse_many(&bundle).into_iter().map(|p| (p.tag, p.contents)).collect();
+    assert_eq!(expected.len(), 4);
+    assert_eq!(documents(bundle.as_str()), expected);
+
+    assert_eq!(documents(""), vec![]);
+    assert_eq!(documents(" \n\t"), vec![]);
+    assert_eq!(documents(&format!("{}{}", gen(0), gen(0))), vec![("DATA".to_owned(), vec![]); 2]);
+
+    let text = format!("subject=CN = A\nissuer=CN = B\n{}\nCertificate:\n    Data:\n{}\ntrailing text\n", blocks[0], blocks[1]);
+    assert_eq!(documents(text.as_str()), &expected[..2]);
+}
+
+#[test]
+fn test_documents_error() {
+    let bundle = format!("{}\n-----BEGIN DATA-----\nAAAA\n", gen(100));
+    let mut documents = Documents::<_, String, BytesContainer<Vec<u8>>>::from_chars(bundle.chars().enumerate());
+
+    assert!(documents.next().unwrap().is_ok());
+    match documents.next() {
+        Some(Err(PemError::Body(_))) => {},
+        _ => panic!("expected a body error"),
+    }
+    assert!(documents.next().is_none());
+
+    fn error(s: &str) -> PemError<usize, Void> {
+        let mut documents = Documents::<_, String, BytesContainer<Vec<u8>>>::from_chars(s.chars().enumerate());
+        documents.next().unwrap().err().unwrap()
+    }
+    assert_eq!(error("-----BEGIN DATA-----\nAAAA\n-----END DATUM-----"), PemError::LabelMismatch{location: 38});
+    assert_eq!(error("-----BEGIN DATA-----\nAAAA\n-----END DAT-----"), PemError::LabelMismatch{location: 39});
+    assert_eq!(error("-----BEGIN DATA-----\nAAAA\n-----END DATA-A-----"), PemError::LabelMismatch{location: 39});
+    assert_eq!(error("-----BEGIN DATA-----\nAAAA\n-----END DATA--"), PemError::EndBoundary(EncapsulationBoundaryError::MissingExpected('-')));
+    assert_eq!(error("-----BEGIN DATA-----\nA!AA\n-----END DATA-----"), PemError::Body(BodyError::InvalidCharacter{location: 22, found: '!'}));
+    assert_eq!(error("-----BEGIN DATA----\n"), PemError::BeginBoundary(EncapsulationBoundaryError::Mismatch{location: 19, expected: '-', found: '\n'}));
+}
+
+#[test]
+fn test_documents_recover() {
+    let blocks = [gen(100), gen(1000), gen(4)];
+    let bundle = format!(
+        "{}\n-----BEGIN DATA----\n{}\n-----BEGIN DATA-----\nA!AA-----BEGIN DATA-----\n-----END DATA-----\n{}\n-----BEGIN DATA-----\nAAAA\n-----END DATUM-----\n-----BEGIN DATA-----\nAA",
+        blocks[0], blocks[1], blocks[2],
+    );
+    let expected: Vec<_> = blocks.iter().map(|b| single(b.as_str())).collect();
+
+    let config = DocumentsConfig{recover: true, ..DocumentsConfig::default()};
+    let documents: Vec<_> = Documents::<_, String, BytesContainer<Vec<u8>>>::from_chars_with_config(bundle.chars().enumerate(), config)
+        .map(|d| d.map(|d| d.body.into()))
+        .collect();
+
+    assert_eq!(documents.len(), 7);
+    assert_eq!(documents[0], Ok(expected[0].clone()));
+    match documents[1] {
+        Err(PemError::BeginBoundary(EncapsulationBoundaryError::Mismatch{expected: '-', found: '\n', ..})) => {},
+        ref e => panic!("expected a BEGIN boundary error, found {:?}", e),
+    }
+    assert_eq!(documents[2], Ok(expected[1].clone()));
+    match documents[3] {
+        Err(PemError::Body(BodyError::InvalidCharacter{found: '!', ..})) => {},
+        ref e => panic!("expected a body error, found {:?}", e),
+    }
+    assert_eq!(documents[4], Ok(expected[2].clone()));
+    match documents[5] {
+        Err(PemError::LabelMismatch{..}) => {},
+        ref e => panic!("expected a label mismatch, found {:?}", e),
+    }
+    assert_eq!(documents[6], Err(PemError::Body(BodyError::MissingExpected('-'))));
+
+    // A block without an END boundary doesn't hide the next one
+    let bundle = "-----BEGIN A-----\nQUJD\n-----BEGIN B-----\nQUJD\n-----END B-----\n";
+    let documents: Vec<_> = Documents::<_, String, BytesContainer<Vec<u8>>>::from_chars_with_config(bundle.chars().enumerate(), config)
+        .map(|d| d.map(|d| (d.label, d.body.into())))
+        .collect();
+    assert_eq!(documents, vec![
+        Err(PemError::EndBoundary(EncapsulationBoundaryError::Mismatch{location: 28, expected: 'E', found: 'B'})),
+        Ok(("B".to_owned(), b"ABC".to_vec())),
+    ]);
+}
+
+fn with_headers(s: &str) -> Vec<u8> {
+    let mut input = s.chars().enumerate();
+
+    let mut label_buf = String::new();
+    {
+        let mut parser =
+            BoundaryParser::from_chars(BoundaryType::Begin, &mut input, &mut label_buf);
+        assert_eq!(parser.next(), None);
+        assert_eq!(parser.complete(), Ok(()));
+    }
+
+    let remainder = {
+        let mut parser = HeaderParser::from_chars(&mut input, DiscardHeaders);
+        assert_eq!(parser.next(), None);
+        parser.complete().unwrap()
+    };
+
+    let v: Result<Vec<u8>, _> = Single::new(remainder.chain_chars(&mut input)).collect();
+
+    {
+        let mut parser = BoundaryParser::from_chars(
+            BoundaryType::End,
+            &mut input,
+            LabelMatcher(label_buf.chars()),
+        );
+        assert_eq!(parser.next(), None);
+        assert_eq!(parser.complete(), Ok(()));
+    }
+
+    v.unwrap()
+}
+
+#[test]
+fn test_headers() {
+    let s = gen(1000);
+    let expected = single(s.as_str());
+    assert_eq!(with_headers(s.as_str()), expected);
+
+    let (begin, body) = s.split_at("-----BEGIN DATA-----".len());
+    let encrypted = format!("{}\nProc-Type: 4,ENCRYPTED\nDEK-Info: AES-128-CBC,\n 2FE8B1A5\n\n{}", begin, body);
+    assert_eq!(with_headers(encrypted.as_str()), expected);
+}
+
+fn body(s: &str, config: BodyConfig) -> Result<Vec<u8>, BodyError<usize, Void>> {
+    let single: Result<Vec<u8>, _> = Single::from_chars_with_config(s.chars().enumerate(), config).collect();
+    let chunked: Result<BytesContainer<Vec<u8>>, _> = Chunked::from_chars_with_config(s.chars().enumerate(), config).collect();
+    assert_eq!(single, chunked.map(BytesContainer::into));
+    single
+}
+
+#[test]
+fn test_strict_padding() {
+    let strict = BodyConfig{padding: Padding::Strict, ..BodyConfig::default()};
+
+    assert_eq!(body("-", strict), Ok(vec![]));
+    assert_eq!(body("QUJD-", strict), Ok(b"ABC".to_vec()));
+    assert_eq!(body("QUI=-", strict), Ok(b"AB".to_vec()));
+    assert_eq!(body("QQ==-", strict), Ok(b"A".to_vec()));
+    assert_eq!(body("QUJD\nQQ=\n=\n-", strict), Ok(b"ABCA".to_vec()));
+
+    assert_eq!(body("QU=JD-", strict), Err(BodyError::InvalidPadding{location: 3}));
+    assert_eq!(body("QUJD====-", strict), Err(BodyError::InvalidPadding{location: 4}));
+    assert_eq!(body("Q===-", strict), Err(BodyError::InvalidPadding{location: 1}));
+    assert_eq!(body("QUI=A-", strict), Err(BodyError::InvalidPadding{location: 4}));
+    assert_eq!(body("QUI-", strict), Err(BodyError::InvalidPadding{location: 3}));
+    assert_eq!(body("QQ=-", strict), Err(BodyError::InvalidPadding{location: 3}));
+
+    // Optional padding only allows leaving out the final '='s
+    let optional = BodyConfig{padding: Padding::Optional, ..BodyConfig::default()};
+    assert_eq!(body("QUI-", optional), Ok(b"AB".to_vec()));
+    assert_eq!(body("QQ=-", optional), Ok(b"A".to_vec()));
+    assert_eq!(body("QUI=-", optional), Ok(b"AB".to_vec()));
+    assert_eq!(body("QU=JD-", optional), Err(BodyError::InvalidPadding{location: 3}));
+    assert_eq!(body("QUI=A-", optional), Err(BodyError::InvalidPadding{location: 4}));
+
+    // Padding is ignored by default
+    assert_eq!(body("QU=JD-", BodyConfig::default()), Ok(b"ABC".to_vec()));
+    assert_eq!(body("QUJD====-", BodyConfig::default()), Ok(b"ABC".to_vec()));
+}
+
+#[test]
+fn test_canonical() {
+    let canonical = BodyConfig{canonical: true, ..BodyConfig::default()};
+    let strict = BodyConfig{padding: Padding::Strict, ..canonical};
+
+    assert_eq!(body("QQ==-", canonical), Ok(b"A".to_vec()));
+    assert_eq!(body("QUI=-", canonical), Ok(b"AB".to_vec()));
+    assert_eq!(body("QUJD-", canonical), Ok(b"ABC".to_vec()));
+    assert_eq!(body("QQ-", canonical), Ok(b"A".to_vec()));
+
+    assert_eq!(body("QR==-", canonical), Err(BodyError::NonCanonical{location: 4}));
+    assert_eq!(body("QR==-", strict), Err(BodyError::NonCanonical{location: 2}));
+    assert_eq!(body("QUJ=-", canonical), Err(BodyError::NonCanonical{location: 4}));
+    assert_eq!(body("QUJ=-", strict), Err(BodyError::NonCanonical{location: 3}));
+    assert_eq!(body("QUJDQ-", canonical), Err(BodyError::NonCanonical{location: 5}));
+
+    // Leftover bits are discarded by default
+    assert_eq!(body("QR==-", BodyConfig::default()), Ok(b"A".to_vec()));
+    assert_eq!(body("QUJ=-", BodyConfig::default()), Ok(b"AB".to_vec()));
+    assert_eq!(body("QUJDQ-", BodyConfig::default()), Ok(b"ABC".to_vec()));
+}
+
+fn profile(s: &str, profile: Profile) -> Result<Vec<u8>, PemError<usize, Void>> {
+    let mut input = s.chars().enumerate();
+    let mut label = String::new();
+    {
+        let mut parser = BoundaryParser::from_chars_with_profile(BoundaryType::Begin, &mut input, &mut label, profile);
+        assert_eq!(parser.next(), None);
+        parser.complete().map_err(PemError::begin)?;
+    }
+
+    let data: Vec<u8> = Single::from_chars_with_config(&mut input, profile.into()).collect::<Result<_, _>>()?;
+
+    let mut parser = BoundaryParser::from_chars_with_profile(BoundaryType::End, &mut input, LabelMatcher(label.chars()), profile);
+    assert_eq!(parser.next(), None);
+    parser.complete().map_err(PemError::end)?;
+    Ok(data)
+}
+
+#[test]
+fn test_profiles() {
+    use Profile::*;
+
+    fn all(s: &str, expected: &[u8]) {
+        for &p in &[Strict, Standard, Lax] {
+            assert_eq!(profile(s, p), Ok(expected.to_vec()), "{:?}", p);
+        }
+    }
+
+    all("-----BEGIN DATA-----\nZm9vYmE=\n-----END DATA-----\n", b"fooba");
+    all("-----BEGIN DATA-----\r\nZm9vYmFy\r\n-----END DATA-----\r\n", b"foobar");
+
+    // Empty body
+    let s = "-----BEGIN DATA-----\n-----END DATA-----\n";
+    assert_eq!(profile(s, Strict), Err(PemError::Body(BodyError::EmptyBody{location: 21})));
+    assert_eq!(profile(s, Standard), Ok(b"".to_vec()));
+    assert_eq!(profile(s, Lax), Ok(b"".to_vec()));
+
+    // Whitespace before BEGIN
+    let s = " \n-----BEGIN DATA-----\nZm9vYmE=\n-----END DATA-----\n";
+    assert_eq!(profile(s, Strict), Err(PemError::BeginBoundary(EncapsulationBoundaryError::Mismatch{location: 0, expected: '-', found: ' '})));
+    assert_eq!(profile(s, Standard), Err(PemError::BeginBoundary(EncapsulationBoundaryError::Mismatch{location: 0, expected: '-', found: ' '})));
+    assert_eq!(profile(s, Lax), Ok(b"fooba".to_vec()));
+
+    // Whitespace after BEGIN
+    let s = "-----BEGIN DATA----- \t\nZm9vYmE=\n-----END DATA-----\n";
+    assert_eq!(profile(s, Strict), Err(PemError::BeginBoundary(EncapsulationBoundaryError::Mismatch{location: 20, expected: '\n', found: ' '})));
+    assert_eq!(profile(s, Standard), Ok(b"fooba".to_vec()));
+    assert_eq!(profile(s, Lax), Ok(b"fooba".to_vec()));
+
+    // The body starts on the BEGIN line
+    let s = "-----BEGIN DATA-----Zm9vYmE=\n-----END DATA-----\n";
+    assert_eq!(profile(s, Strict), Err(PemError::BeginBoundary(EncapsulationBoundaryError::Mismatch{location: 20, expected: '\n', found: 'Z'})));
+    assert_eq!(profile(s, Standard), Err(PemError::BeginBoundary(EncapsulationBoundaryError::Mismatch{location: 20, expected: '\n', found: 'Z'})));
+    assert_eq!(profile(s, Lax), Ok(b"fooba".to_vec()));
+
+    // END on the last line of the body
+    let s = "-----BEGIN DATA-----\nZm9vYmE=-----END DATA-----\n";
+    assert_eq!(profile(s, Strict), Err(PemError::Body(BodyError::InvalidCharacter{location: 29, found: '-'})));
+    assert_eq!(profile(s, Standard), Err(PemError::Body(BodyError::InvalidCharacter{location: 29, found: '-'})));
+    assert_eq!(profile(s, Lax), Ok(b"fooba".to_vec()));
+
+    // Empty lines
+    let s = "-----BEGIN DATA-----\nZm9vYmE=\n\n\n-----END DATA-----\n";
+    assert_eq!(profile(s, Strict), Err(PemError::Body(BodyError::EmptyLine{location: 30})));
+    assert_eq!(profile(s, Standard), Ok(b"fooba".to_vec()));
+    assert_eq!(profile(s, Lax), Ok(b"fooba".to_vec()));
+    let s = "-----BEGIN DATA-----\r\nZm9vYmE=\r\n\r\n-----END DATA-----\r\n";
+    assert_eq!(profile(s, Strict), Err(PemError::Body(BodyError::EmptyLine{location: 32})));
+    assert_eq!(profile(s, Standard), Ok(b"fooba".to_vec()));
+    let s = "-----BEGIN DATA-----\r\n\r\nZm9vYmE=\r\n-----END DATA-----\r\n";
+    assert_eq!(profile(s, Strict), Err(PemError::Body(BodyError::EmptyLine{location: 22})));
+    assert_eq!(profile(s, Standard), Ok(b"fooba".to_vec()));
+
+    // No line ending after END
+    let s = "-----BEGIN DATA-----\nZm9vYmE=\n-----END DATA-----";
+    assert_eq!(profile(s, Strict), Err(PemError::EndBoundary(EncapsulationBoundaryError::MissingExpected('\n'))));
+    assert_eq!(profile(s, Standard), Ok(b"fooba".to_vec()));
+    assert_eq!(profile(s, Lax), Ok(b"fooba".to_vec()));
+
+    // Text after END
+    let s = "-----BEGIN DATA-----\nZm9vYmE=\n-----END DATA----- x\n";
+    assert_eq!(profile(s, Strict), Err(PemError::EndBoundary(EncapsulationBoundaryError::Mismatch{location: 48, expected: '\n', found: ' '})));
+    assert_eq!(profile(s, Standard), Err(PemError::EndBoundary(EncapsulationBoundaryError::Mismatch{location: 49, expected: '\n', found: 'x'})));
+    assert_eq!(profile(s, Lax), Ok(b"fooba".to_vec()));
+
+    // Missing padding
+    let s = "-----BEGIN DATA-----\nZm9vYmE\n-----END DATA-----\n";
+    assert_eq!(profile(s, Strict), Err(PemError::Body(BodyError::InvalidPadding{location: 29})));
+    assert_eq!(profile(s, Standard), Ok(b"fooba".to_vec()));
+    assert_eq!(profile(s, Lax), Ok(b"fooba".to_vec()));
+    let s = "-----BEGIN DATA-----\nZg=\n-----END DATA-----\n";
+    assert_eq!(profile(s, Strict), Err(PemError::Body(BodyError::InvalidPadding{location: 25})));
+    assert_eq!(profile(s, Standard), Ok(b"f".to_vec()));
+    assert_eq!(profile(s, Lax), Ok(b"f".to_vec()));
+
+    // Misplaced padding
+    let s = "-----BEGIN DATA-----\nZm9v=YmE\n-----END DATA-----\n";
+    for &p in &[Strict, Standard, Lax] {
+        assert_eq!(profile(s, p), Err(PemError::Body(BodyError::InvalidPadding{location: 25})));
+    }
+
+    // Lines of 64 characters
+    let data: Vec<u8> = (0..100).collect();
+    let s: String = Encoder::new(data.iter().cloned(), "DATA".chars()).collect();
+    all(&s, &data);
+
+    let unwrapped = EncoderConfig{line_length: 0, ..EncoderConfig::default()};
+    let s: String = Encoder::with_config(data.iter().cloned(), "DATA".chars(), unwrapped).collect();
+    assert_eq!(profile(&s, Strict), Err(PemError::Body(BodyError::LineTooLong{location: 85})));
+    assert_eq!(profile(&s, Standard), Ok(data.clone()));
+    assert_eq!(profile(&s, Lax), Ok(data.clone()));
+
+    let short = EncoderConfig{line_length: 60, ..EncoderConfig::default()};
+    let s: String = Encoder::with_config(data.iter().cloned(), "DATA".chars(), short).collect();
+    assert_eq!(profile(&s, Strict), Err(PemError::Body(BodyError::LineTooShort{location: 82})));
+    assert_eq!(profile(&s, Standard), Ok(data.clone()));
+    assert_eq!(profile(&s, Lax), Ok(data.clone()));
+
+    // Whitespace in the body
+    let s = "-----BEGIN DATA-----\nZm9v YmE=\n-----END DATA-----\n";
+    assert_eq!(profile(s, Strict), Err(PemError::Body(BodyError::InvalidCharacter{location: 25, found: ' '})));
+    assert_eq!(profile(s, Standard), Err(PemError::Body(BodyError::MisplacedWhitespace{location: 26})));
+    assert_eq!(profile(s, Lax), Ok(b"fooba".to_vec()));
+    let s = "-----BEGIN DATA-----\nZm9v\x0BYmE=\n-----END DATA-----\n";
+    assert_eq!(profile(s, Strict), Err(PemError::Body(BodyError::InvalidCharacter{location: 25, found: '\x0B'})));
+    assert_eq!(profile(s, Standard), Err(PemError::Body(BodyError::InvalidCharacter{location: 25, found: '\x0B'})));
+    assert_eq!(profile(s, Lax), Ok(b"fooba".to_vec()));
+    let s = "-----BEGIN DATA-----\nZm9v \t\nYmE=\t\n-----END DATA-----\n";
+    assert_eq!(profile(s, Strict), Err(PemError::Body(BodyError::InvalidCharacter{location: 25, found: ' '})));
+    assert_eq!(profile(s, Standard), Ok(b"fooba".to_vec()));
+    assert_eq!(profile(s, Lax), Ok(b"fooba".to_vec()));
+    let s = "-----BEGIN DATA-----\nZm9v\u{2028}YmE=\n-----END DATA-----\n";
+    for &p in &[Strict, Standard, Lax] {
+        assert_eq!(profile(s, p), Err(PemError::Body(BodyError::InvalidCharacter{location: 25, found: '\u{2028}'})));
+    }
+
+    // Non-zero leftover bits
+    let s = "-----BEGIN DATA-----\nZm9vYmF=\n-----END DATA-----\n";
+    assert_eq!(profile(s, Strict), Err(PemError::Body(BodyError::NonCanonical{location: 28})));
+    assert_eq!(profile(s, Standard), Ok(b"fooba".to_vec()));
+    assert_eq!(profile(s, Lax), Ok(b"fooba".to_vec()));
+}
+
+#[test]
+fn test_line_length() {
+    let limited = BodyConfig{max_line_length: 4, ..BodyConfig::default()};
+    let exact = BodyConfig{exact_lines: true, ..limited};
+
+    assert_eq!(body("QUJD\nQUJD\nQQ\n-", limited), Ok(b"ABCABCA".to_vec()));
+    assert_eq!(body("QUJD\r\nQU \tJD\r\n\r\nQQ==\r\n-", limited), Ok(b"ABCABCA".to_vec()));
+    assert_eq!(body("QUJDQ\nQQ\n-", limited), Err(BodyError::LineTooLong{location: 4}));
+    assert_eq!(body("QQ\nQUI==\n-", limited), Err(BodyError::LineTooLong{location: 7}));
+
+    assert_eq!(body("QUJD\nQUJD\nQQ\n-", exact), Ok(b"ABCABCA".to_vec()));
+    assert_eq!(body("QUJD\r\nQUJD\r\n-", exact), Ok(b"ABCABC".to_vec()));
+    assert_eq!(body("QQ\nQUJD\n-", exact), Err(BodyError::LineTooShort{location: 3}));
+    assert_eq!(body("QUJD\nQU\n\nJD\n-", exact), Err(BodyError::LineTooShort{location: 9}));
+
+    // Lines are unlimited by default
+    let long = "QUJD".repeat(1000) + "-";
+    assert_eq!(body(&long, BodyConfig::default()).map(|v| v.len()), Ok(3000));
+
+    // Without a limit, there's no length for lines to match
+    let unlimited = BodyConfig{exact_lines: true, ..BodyConfig::default()};
+    assert_eq!(body("QUJD\nQUJ\nDQQ\n-", unlimited), Ok(b"ABCABCA".to_vec()));
+}
+
+#[test]
+fn test_whitespace() {
+    let line_breaks = BodyConfig{whitespace: Whitespace::LineBreaks, ..BodyConfig::default()};
+    let ascii = BodyConfig{whitespace: Whitespace::Ascii, ..BodyConfig::default()};
+
+    for &config in &[line_breaks, ascii, BodyConfig::default()] {
+        assert_eq!(body("QUJD\r\nQUJD\n\n-", config), Ok(b"ABCABC".to_vec()));
+    }
+
+    assert_eq!(body("QU JD\n-", line_breaks), Err(BodyError::InvalidCharacter{location: 2, found: ' '}));
+    assert_eq!(body("QU JD\n-", ascii), Ok(b"ABC".to_vec()));
+    assert_eq!(body("QU\tJD\x0B\x0C\n-", ascii), Ok(b"ABC".to_vec()));
+
+    assert_eq!(body("QU\u{a0}JD\n-", ascii), Err(BodyError::InvalidCharacter{location: 2, found: '\u{a0}'}));
+    assert_eq!(body("QUJD\u{2028}QUJD-", ascii), Err(BodyError::InvalidCharacter{location: 4, found: '\u{2028}'}));
+
+    // Unicode whitespace is allowed by default
+    assert_eq!(body("QU\u{a0}JD\n-", BodyConfig::default()), Ok(b"ABC".to_vec()));
+    assert_eq!(body("QUJD\u{2028}QUJD-", BodyConfig::default()), Ok(b"ABCABC".to_vec()));
+}
+
+#[test]
+fn test_trailing_whitespace() {
+    let trailing = BodyConfig{whitespace: Whitespace::Trailing, ..BodyConfig::default()};
+
+    assert_eq!(body("QUJD \t\r\nQUJD\t\n \n-", trailing), Ok(b"ABCABC".to_vec()));
+    assert_eq!(body("QU JD\n-", trailing), Err(BodyError::MisplacedWhitespace{location: 3}));
+    assert_eq!(body("QUJD\n\tQUJD\n-", trailing), Err(BodyError::MisplacedWhitespace{location: 6}));
+    assert_eq!(body("QUJD \n -", trailing), Err(BodyError::MisplacedWhitespace{location: 7}));
+    assert_eq!(body("QU\x0CJD\n-", trailing), Err(BodyError::InvalidCharacter{location: 2, found: '\x0C'}));
+}
+
+#[test]
+fn test_lines() {
+    let end = BodyConfig{lines: Lines::EndOnNewLine, ..BodyConfig::default()};
+    let strict = BodyConfig{lines: Lines::Strict, ..BodyConfig::default()};
+
+    for &config in &[end, strict, BodyConfig::default()] {
+        assert_eq!(body("QUJD\nQUJD\r\n-", config), Ok(b"ABCABC".to_vec()));
+        assert_eq!(body("\nQUJD\rQUJD\n-", config), Ok(b"ABCABC".to_vec()));
+    }
+
+    // The END boundary must start a line
+    assert_eq!(body("QUJD-", BodyConfig::default()), Ok(b"ABC".to_vec()));
+    assert_eq!(body("QUJD-", end), Err(BodyError::InvalidCharacter{location: 4, found: '-'}));
+    assert_eq!(body("QUJD-", strict), Err(BodyError::InvalidCharacter{location: 4, found: '-'}));
+
+    // Empty lines and bodies
+    for &config in &[end, BodyConfig::default()] {
+        assert_eq!(body("QUJD\n\nQUJD\r\n\r\n-", config), Ok(b"ABCABC".to_vec()));
+        assert_eq!(body("-", config), Ok(b"".to_vec()));
+    }
+    assert_eq!(body("QUJD\n\nQUJD\n-", strict), Err(BodyError::EmptyLine{location: 5}));
+    assert_eq!(body("QUJD\r\n\r\nQUJD\n-", strict), Err(BodyError::EmptyLine{location: 6}));
+    assert_eq!(body("QUJD\r\rQUJD\n-", strict), Err(BodyError::EmptyLine{location: 5}));
+    assert_eq!(body("\r\rQUJD\n-", strict), Err(BodyError::EmptyLine{location: 0}));
+    assert_eq!(body("-", strict), Err(BodyError::EmptyBody{location: 0}));
+    assert_eq!(body("\n-", strict), Err(BodyError::EmptyBody{location: 1}));
+}
+
+#[test]
+fn test_from_bytes() {
+    let s = gen(1000);
+    let mut input = s.as_bytes().iter().cloned().enumerate();
+
+    let mut label_buf = String::new();
+    {
+        let mut parser = BoundaryParser::from_bytes(BoundaryType::Begin, &mut input, &mut label_buf);
+        assert_eq!(parser.next(), None);
+        assert_eq!(parser.complete(), Ok(()));
+    }
+    assert_eq!(label_buf, "DATA");
+
+    let v: Result<BytesContainer<Vec<u8>>, _> = Chunked::from_bytes(&mut input).collect();
+    assert_eq!(v.map(BytesContainer::into), Ok(single(s.as_str())));
+
+    {
+        let mut parser = BoundaryParser::from_bytes(BoundaryType::End, &mut input, LabelMatcher(label_buf.chars()));
+        assert_eq!(parser.next(), None);
+        assert_eq!(parser.complete(), Ok(()));
+    }
+
+    // Non-ASCII bytes are stream errors, so parsing can carry on past them
+    let mut single = Single::from_bytes(b"QU\xffJD-".iter().cloned().enumerate());
+    assert_eq!(single.next(), Some(Ok(b'A')));
+    assert_eq!(single.next(), Some(Err(BodyError::SourceError(NonAsciiError{location: 2, found: 0xff}))));
+    assert!(single.eq(b"BC".iter().map(|&b| Ok(b))));
+}
+
+#[test]
+fn test_locations() {
+    let input = "QUJD\r\nQU!D\r\n-";
+    let v: Result<Vec<u8>, _> = Single::from_chars(Locations::new(input.chars())).collect();
+    let location = Location{line: 2, column: 3, byte_offset: 8, char_offset: 8};
+    assert_eq!(v, Err(BodyError::InvalidCharacter{location, found: '!'}));
+    assert_eq!(location.to_string(), "line 2, column 3");
+}
+
+#[test]
+fn test_display() {
+    let input = "-----BEGIN DATA-----\nQUJD\n----x";
+    let mut input = Locations::new(input.chars());
+    {
+        let mut parser = BoundaryParser::from_chars(BoundaryType::Begin, &mut input, DiscardLabel);
+        assert_eq!(parser.next(), None);
+        assert_eq!(parser.complete(), Ok(()));
+    }
+    let v: Result<Vec<u8>, _> = Single::from_chars(&mut input).collect();
+    assert_eq!(v, Ok(b"ABC".to_vec()));
+    let mut parser = BoundaryParser::from_chars(BoundaryType::End, &mut input, DiscardLabel);
+    assert_eq!(parser.next(), None);
+    let e = parser.complete().unwrap_err();
+    assert_eq!(e.to_string(), "expected '-' at line 3, column 5, found 'x'");
+
+    let e = PemError::<usize, Void>::Body(BodyError::InvalidPadding{location: 3});
+    assert_eq!(e.to_string(), "invalid body: invalid padding at 3");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_error_source() {
+    use std::error::Error;
+
+    // Stream errors are available through `source`
+    let e: Box<dyn Error> = Box::new(BodyError::<usize, _>::SourceError(NonAsciiError{location: 7, found: 0xff}));
+    assert_eq!(e.to_string(), "error reading the input");
+    assert_eq!(e.source().unwrap().to_string(), "non-ASCII byte 0xff at 7");
 }
 
 #[test]